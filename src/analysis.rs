@@ -0,0 +1,440 @@
+//! Name resolution over a parsed program: where each variable and function
+//! is defined, and which definition each use of a name refers to.
+//!
+//! Scopes follow the runtime: every block has its own, functions and
+//! variables live apart, and the bindings of a match arm are local to it.
+//! Names used in function bodies may refer to definitions that come after
+//! the function, since they are only looked up when it is called.
+
+use crate::{
+    lexer::{Lexer, Position, Token, TokenKind},
+    parser::*,
+    runtime::Arity,
+};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Variable,
+    Function,
+    Parameter,
+    Binding,
+}
+
+/// A name defined by the program.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Where the name is written in its definition.
+    pub position: Position,
+    /// The whole defining statement of variables and functions, or just the
+    /// name of parameters and bindings.
+    pub span: Span,
+    /// A summary of the definition, such as `fn f(a, b)` or `x = 1`.
+    pub detail: String,
+    /// The index of the function the symbol is defined in, if any.
+    pub parent: Option<usize>,
+    /// The index of the definition of the same name in an outer scope that
+    /// this one hides, if any.
+    pub shadows: Option<usize>,
+    /// How many arguments a function takes.
+    pub arity: Option<Arity>,
+}
+
+/// An occurrence of a name, including the one in its definition.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub name: String,
+    /// Whether the name is of a function rather than a variable.
+    pub function: bool,
+    /// Whether the name is defined or assigned to, rather than used.
+    pub assignment: bool,
+    pub position: Position,
+    /// The index of the definition referred to, or `None` if the program
+    /// doesn't define the name, as is the case for builtins.
+    pub symbol: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub symbols: Vec<Symbol>,
+    pub references: Vec<Reference>,
+}
+
+impl Analysis {
+    /// Resolves the names of `ast`, which must have been parsed from
+    /// `source`.
+    ///
+    /// ```
+    /// use kotoba::{analysis::Analysis, lexer::Position, parser::Parser};
+    ///
+    /// let source = "fn f(a): ret a + x;\nx = 1, f(x)";
+    /// let analysis = Analysis::new(source, &Parser::new(source).parse());
+    ///
+    /// let x = analysis.reference_at(Position { line: 1, character: 18 }).unwrap();
+    /// let definition = &analysis.symbols[x.symbol.unwrap()];
+    /// assert_eq!(definition.detail, "x = 1");
+    /// assert_eq!(definition.position, Position { line: 2, character: 1 });
+    /// ```
+    pub fn new(source: &str, ast: &AstNode) -> Self {
+        let mut resolver = Resolver {
+            source,
+            tokens: Lexer::new(source).collect(),
+            scopes: vec![Scope::default()],
+            function: None,
+            deferred: vec![],
+            analysis: Analysis::default(),
+        };
+        resolver.walk(ast);
+        resolver.resolve_deferred();
+        resolver.analysis
+    }
+
+    /// Returns the occurrence of a name that `position` falls on.
+    pub fn reference_at(&self, position: Position) -> Option<&Reference> {
+        self.references.iter().find(|r| {
+            r.position.line == position.line
+                && r.position.character <= position.character
+                && position.character < r.position.character + r.name.len()
+        })
+    }
+}
+
+#[derive(Default)]
+struct Scope {
+    variables: HashMap<String, usize>,
+    functions: HashMap<String, usize>,
+}
+
+struct Resolver<'s> {
+    source: &'s str,
+    tokens: Vec<Token>,
+    scopes: Vec<Scope>,
+    /// The function whose body is being walked.
+    function: Option<usize>,
+    /// References made from function bodies that didn't resolve yet.
+    deferred: Vec<usize>,
+    analysis: Analysis,
+}
+
+impl Resolver<'_> {
+    fn walk(&mut self, node: &AstNode) {
+        match &node.kind {
+            NodeKind::ProgramRoot(stmts) => stmts.iter().for_each(|s| self.walk(s)),
+            NodeKind::Program(stmts) => {
+                self.scopes.push(Scope::default());
+                stmts.iter().for_each(|s| self.walk(s));
+                self.scopes.pop();
+            }
+            NodeKind::Number(_)
+            | NodeKind::Boolean(_)
+            | NodeKind::StringLiteral(_)
+            | NodeKind::Nil => {}
+            NodeKind::Interpolation(nodes) | NodeKind::List(nodes) => {
+                nodes.iter().for_each(|n| self.walk(n))
+            }
+            NodeKind::Grouping(expr) | NodeKind::RetStmt(expr) => self.walk(expr),
+            NodeKind::Identifier(name) => self.reference(name, false, false, node.span.start),
+            NodeKind::FnCall {
+                identifier,
+                args,
+                named,
+            } => {
+                self.reference(identifier, true, false, node.span.start);
+                args.iter().for_each(|a| self.walk(a));
+                named.iter().for_each(|(_, a)| self.walk(a));
+            }
+            NodeKind::MethodCall { receiver, args, .. } => {
+                self.walk(receiver);
+                args.iter().for_each(|a| self.walk(a));
+            }
+            NodeKind::UnaryExpr { operand, .. } => self.walk(operand),
+            NodeKind::BinaryExpr { lhs, rhs, .. } => {
+                self.walk(lhs);
+                self.walk(rhs);
+            }
+            NodeKind::Assignment {
+                identifier,
+                operand,
+                nonlocal,
+                ..
+            } => {
+                self.walk(operand);
+
+                let defined = self
+                    .scopes
+                    .last()
+                    .unwrap()
+                    .variables
+                    .contains_key(identifier);
+                if *nonlocal {
+                    let position = self.find_name(node.span.start, identifier);
+                    self.reference(identifier, false, true, position);
+                } else if defined {
+                    self.reference(identifier, false, true, node.span.start);
+                } else {
+                    let detail = self.summary(node.span, identifier);
+                    self.define(
+                        identifier,
+                        SymbolKind::Variable,
+                        node.span.start,
+                        node.span,
+                        detail,
+                    );
+                }
+            }
+            NodeKind::IfStmt {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.walk(condition);
+                self.walk(then_body);
+                if let Some(else_body) = else_body {
+                    self.walk(else_body);
+                }
+            }
+            NodeKind::WhileStmt { condition, body } => {
+                self.walk(condition);
+                self.walk(body);
+            }
+            NodeKind::FnStmt {
+                identifier,
+                params,
+                returns,
+                body,
+            } => {
+                let position = self.find_name(node.span.start, identifier);
+                let signature = params
+                    .iter()
+                    .map(|p| self.param_summary(p))
+                    .collect::<Vec<_>>();
+                let mut detail = format!("fn {}({})", identifier, signature.join(", "));
+                if let Some(returns) = returns {
+                    detail.push_str(&format!(" -> {}", returns));
+                }
+                let function = self.define(
+                    identifier,
+                    SymbolKind::Function,
+                    position,
+                    node.span,
+                    detail,
+                );
+                self.analysis.symbols[function].arity = Some(Arity::of_params(params));
+
+                let enclosing = self.function.replace(function);
+                self.scopes.push(Scope::default());
+                let mut after = past(position, identifier);
+                for param in params {
+                    let name = &param.name;
+                    let position = self.find_name(after, name);
+                    // defaults see the parameters before theirs
+                    if let Some(default) = &param.default {
+                        self.walk(default);
+                    }
+                    let detail = format!("parameter {} of {}", name, identifier);
+                    self.define_name(name, SymbolKind::Parameter, position, detail);
+                    after = match &param.default {
+                        Some(default) => default.span.end,
+                        None => self.past_annotation(past(position, name)),
+                    };
+                }
+                // parameters live in the same scope as the body, as far as
+                // scripts can tell
+                match &body.kind {
+                    NodeKind::Program(stmts) => stmts.iter().for_each(|s| self.walk(s)),
+                    _ => self.walk(body),
+                }
+                self.scopes.pop();
+                self.function = enclosing;
+            }
+            NodeKind::MatchStmt { scrutinee, arms } => {
+                self.walk(scrutinee);
+
+                let mut after = scrutinee.span.end;
+                for arm in arms {
+                    self.scopes.push(Scope::default());
+                    let mut bindings = vec![];
+                    bindings_of(&arm.pattern, &mut bindings);
+                    for binding in bindings {
+                        let position = self.find_name(after, binding);
+                        let detail = format!("binding {} of a match arm", binding);
+                        self.define_name(binding, SymbolKind::Binding, position, detail);
+                        after = past(position, binding);
+                    }
+
+                    if let Some(guard) = &arm.guard {
+                        self.walk(guard);
+                    }
+                    self.walk(&arm.body);
+                    after = arm.body.span.end;
+                    self.scopes.pop();
+                }
+            }
+        }
+    }
+
+    fn define(
+        &mut self,
+        name: &str,
+        kind: SymbolKind,
+        position: Position,
+        span: Span,
+        detail: String,
+    ) -> usize {
+        let function = kind == SymbolKind::Function;
+        let index = self.analysis.symbols.len();
+        self.analysis.symbols.push(Symbol {
+            name: name.to_owned(),
+            kind,
+            position,
+            span,
+            detail,
+            parent: self.function,
+            shadows: self.lookup(name, function),
+            arity: None,
+        });
+
+        let scope = self.scopes.last_mut().unwrap();
+        if function {
+            scope.functions.insert(name.to_owned(), index);
+        } else {
+            scope.variables.insert(name.to_owned(), index);
+        }
+
+        self.analysis.references.push(Reference {
+            name: name.to_owned(),
+            function,
+            assignment: true,
+            position,
+            symbol: Some(index),
+        });
+        index
+    }
+
+    /// Defines a symbol made of just its name.
+    fn define_name(&mut self, name: &str, kind: SymbolKind, position: Position, detail: String) {
+        let span = Span {
+            start: position,
+            end: past(position, name),
+        };
+        self.define(name, kind, position, span, detail);
+    }
+
+    /// Returns the innermost definition of `name` visible from the current
+    /// scope.
+    fn lookup(&self, name: &str, function: bool) -> Option<usize> {
+        self.scopes.iter().rev().find_map(|scope| {
+            if function {
+                scope.functions.get(name).copied()
+            } else {
+                scope.variables.get(name).copied()
+            }
+        })
+    }
+
+    fn reference(&mut self, name: &str, function: bool, assignment: bool, position: Position) {
+        let symbol = self.lookup(name, function);
+        if symbol.is_none() && self.function.is_some() {
+            self.deferred.push(self.analysis.references.len());
+        }
+        self.analysis.references.push(Reference {
+            name: name.to_owned(),
+            function,
+            assignment,
+            position,
+            symbol,
+        });
+    }
+
+    /// Resolves references from function bodies to the global definitions
+    /// that come after the function.
+    fn resolve_deferred(&mut self) {
+        let globals = self.scopes.pop().unwrap();
+        for index in self.deferred.drain(..) {
+            let reference = &mut self.analysis.references[index];
+            let names = if reference.function {
+                &globals.functions
+            } else {
+                &globals.variables
+            };
+            reference.symbol = names.get(&reference.name).copied();
+        }
+    }
+
+    /// Returns the position of the first occurrence of the identifier `name`
+    /// at or after `after`, which is `after` itself if there is none.
+    fn find_name(&self, after: Position, name: &str) -> Position {
+        let start = self.tokens.partition_point(|t| {
+            (t.position.line, t.position.character) < (after.line, after.character)
+        });
+        self.tokens[start..]
+            .iter()
+            .find(|t| matches!(&t.kind, TokenKind::Identifier(id) if id == name))
+            .map_or(after, |t| t.position)
+    }
+
+    /// Returns the position after the `: type` annotation starting at
+    /// `after`, or `after` itself if there is none.
+    fn past_annotation(&self, after: Position) -> Position {
+        let start = self.tokens.partition_point(|t| {
+            (t.position.line, t.position.character) < (after.line, after.character)
+        });
+        match &self.tokens[start..] {
+            [colon, type_name, ..] if colon.kind == TokenKind::Colon => type_name.end,
+            _ => after,
+        }
+    }
+
+    /// Returns the source of `span`, if it is on a single line.
+    fn snippet(&self, span: Span) -> Option<&str> {
+        let line = self.source.lines().nth(span.start.line - 1)?;
+        if span.start.line != span.end.line {
+            return None;
+        }
+        line.get(span.start.character - 1..span.end.character - 1)
+    }
+
+    /// Returns the source of a definition spanning a single line, or else
+    /// just its name.
+    fn summary(&self, span: Span, name: &str) -> String {
+        match self.snippet(span) {
+            Some(source) => source.to_owned(),
+            None => format!("{} = ...", name),
+        }
+    }
+
+    /// Returns a parameter the way it is declared, such as `b: num = 2`.
+    fn param_summary(&self, param: &Param) -> String {
+        let mut summary = if param.rest {
+            format!("*{}", param.name)
+        } else {
+            param.name.clone()
+        };
+        if let Some(annotation) = param.annotation {
+            summary.push_str(&format!(": {}", annotation));
+        }
+        if let Some(default) = &param.default {
+            let default = self.snippet(default.span).unwrap_or("...");
+            summary.push_str(&format!(" = {}", default));
+        }
+        summary
+    }
+}
+
+/// Returns the position right after `name`, written at `position`.
+pub(crate) fn past(position: Position, name: &str) -> Position {
+    Position {
+        line: position.line,
+        character: position.character + name.len(),
+    }
+}
+
+pub(crate) fn bindings_of<'p>(pattern: &'p Pattern, bindings: &mut Vec<&'p str>) {
+    match pattern {
+        Pattern::Binding(name) => bindings.push(name),
+        Pattern::List(items) => items.iter().for_each(|p| bindings_of(p, bindings)),
+        _ => {}
+    }
+}
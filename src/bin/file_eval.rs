@@ -1,13 +1,88 @@
-use kotoba::{parser::Parser, runtime::*};
-use std::{env, fs};
+use kotoba::{checker, lexer::Lexer, parser::Parser, runtime::*};
+use std::{env, fs, process};
+
+const USAGE: &str =
+    "usage: file_eval [--dump-tokens | --dump-ast[=sexpr|json|debug]] FILE [ARGS...]";
+
+/// What to do with the file, instead of the default of running it.
+enum Mode {
+    Run,
+    DumpTokens,
+    DumpAst(AstFormat),
+}
+
+enum AstFormat {
+    Sexpr,
+    Json,
+    Debug,
+}
 
 fn main() {
-    let path = env::args()
-        .nth(1)
+    let mut args = env::args().skip(1).peekable();
+
+    let mode = match args.peek().map(String::as_str) {
+        Some("--dump-tokens") => Mode::DumpTokens,
+        Some("--dump-ast") => Mode::DumpAst(AstFormat::Sexpr),
+        Some(flag) if flag.starts_with("--dump-ast=") => match &flag["--dump-ast=".len()..] {
+            "sexpr" => Mode::DumpAst(AstFormat::Sexpr),
+            "json" => Mode::DumpAst(AstFormat::Json),
+            "debug" => Mode::DumpAst(AstFormat::Debug),
+            other => {
+                eprintln!("unknown AST format {}\n{}", other, USAGE);
+                process::exit(2);
+            }
+        },
+        Some(flag) if flag.starts_with("--") => {
+            eprintln!("unknown option {}\n{}", flag, USAGE);
+            process::exit(2);
+        }
+        _ => Mode::Run,
+    };
+    if !matches!(mode, Mode::Run) {
+        args.next();
+    }
+
+    let path = args
+        .next()
         .expect("No file provided. Did you mean to run in REPL mode instead?");
 
     let source = fs::read_to_string(path).unwrap();
 
-    let ast = Parser::new(&source).parse();
-    Env::eval(Env::new(), &ast);
+    if let Mode::DumpTokens = mode {
+        for token in Lexer::new(&source) {
+            println!("{}-{} {:?}", token.position, token.end, token.kind);
+        }
+        return;
+    }
+
+    let mut parser = Parser::new(&source);
+    let ast = parser.try_parse();
+    for warning in parser.warnings() {
+        eprintln!("{}", warning);
+    }
+    let ast = ast.unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    match mode {
+        Mode::DumpAst(AstFormat::Sexpr) => println!("{}", ast.to_sexpr()),
+        Mode::DumpAst(AstFormat::Json) => println!("{}", ast.to_json()),
+        Mode::DumpAst(AstFormat::Debug) => println!("{:#?}", ast),
+        _ => {
+            let env = EnvBuilder::new().args(args.collect()).build();
+            let errors = checker::check_in(&ast, &env.borrow());
+            if !errors.is_empty() {
+                for error in errors {
+                    eprintln!("{}", error);
+                }
+                process::exit(1);
+            }
+
+            if let Err(err) = Env::eval(env, &ast) {
+                eprintln!("runtime error: {}", err);
+                process::exit(1);
+            }
+        }
+    }
 }
@@ -0,0 +1,391 @@
+//! The kotoba command line: runs programs and the interactive prompt, and
+//! the tools that inspect, check, format and test programs.
+
+use kotoba::{
+    checker, formatter,
+    lexer::Lexer,
+    parser::{AstNode, Parser},
+    repl,
+    runtime::*,
+};
+use std::{
+    cell::RefCell,
+    env, fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    rc::Rc,
+};
+
+const USAGE: &str = "usage: kotoba [COMMAND] [ARGS...]
+
+Commands:
+  run INPUT [ARGS...]    run a program, which `args()` returns ARGS to
+  repl                   start the interactive prompt, the default
+  check INPUT...         report syntax errors and warnings, without running
+  fmt [--check] [FILE...]
+                         format files in place, or standard input to
+                         standard output; with --check, only report
+                         files that aren't formatted
+  ast [--format=sexpr|json|debug] INPUT
+                         print the syntax tree of a program
+  tokens INPUT           print the tokens of a program
+  test [PATH...]         run the programs in PATHs, the current directory
+                         by default, comparing what they print with the
+                         .out file next to them, if any; they can't
+                         read the standard input or exit
+
+INPUT is the path of a file, `-` for standard input, or `-e CODE` for code
+given inline. `kotoba -e CODE` runs CODE and prints its value, unless nil.
+
+The exit status is 0 on success, 1 if a program or a check failed, and 2
+if the command line was wrong or an input couldn't be read.";
+
+/// Where a program is read from.
+enum Input {
+    File(String),
+    Stdin,
+    Inline(String),
+}
+
+impl Input {
+    /// Takes the input from the arguments, which is `-e CODE`, `-` or a path.
+    fn from_args(args: &mut impl Iterator<Item = String>) -> Input {
+        match args.next().as_deref() {
+            Some("-e") => match args.next() {
+                Some(code) => Input::Inline(code),
+                None => usage_error("-e needs code"),
+            },
+            Some("-") => Input::Stdin,
+            Some(flag) if flag.starts_with('-') => usage_error(&format!("unknown option {}", flag)),
+            Some(path) => Input::File(path.to_owned()),
+            None => usage_error("no input given"),
+        }
+    }
+
+    /// The name errors are reported with.
+    fn name(&self) -> &str {
+        match self {
+            Input::File(path) => path,
+            Input::Stdin => "<stdin>",
+            Input::Inline(_) => "<inline>",
+        }
+    }
+
+    /// Reads the source code, exiting if it can't be.
+    fn read(&self) -> String {
+        let source = match self {
+            Input::File(path) => fs::read_to_string(path),
+            Input::Stdin => {
+                let mut source = String::new();
+                io::stdin().read_to_string(&mut source).map(|_| source)
+            }
+            Input::Inline(code) => Ok(code.clone()),
+        };
+        source.unwrap_or_else(|err| {
+            eprintln!("{}: {}", self.name(), err);
+            process::exit(2);
+        })
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let status = match args.next().as_deref() {
+        None | Some("repl") => Ok(repl()),
+        Some("run") => run(&mut out, Input::from_args(&mut args), args.collect()),
+        Some("-e") => match args.next() {
+            Some(code) => run(&mut out, Input::Inline(code), args.collect()),
+            None => usage_error("-e needs code"),
+        },
+        Some("check") => Ok(check(args)),
+        Some("fmt") => fmt(&mut out, args),
+        Some("ast") => ast(&mut out, args),
+        Some("tokens") => tokens(&mut out, Input::from_args(&mut args)),
+        Some("test") => test(&mut out, args.collect()),
+        Some("-h" | "--help" | "help") => writeln!(out, "{}", USAGE).map(|()| 0),
+        Some(other) => usage_error(&format!("unknown command {}", other)),
+    };
+
+    // output that nobody reads anymore, as with `| head`, ends the command
+    let status = match status.and_then(|status| out.flush().map(|()| status)) {
+        Ok(status) => status,
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => 0,
+        Err(err) => {
+            eprintln!("kotoba: {}", err);
+            1
+        }
+    };
+    process::exit(status);
+}
+
+fn repl() -> i32 {
+    match repl::run() {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("repl: {}", err);
+            1
+        }
+    }
+}
+
+/// Runs a program after checking its types. Inline code has its value
+/// printed as well.
+fn run(out: &mut impl Write, input: Input, args: Vec<String>) -> io::Result<i32> {
+    let ast = match parse(&input) {
+        Some(ast) => ast,
+        None => return Ok(1),
+    };
+    let env = EnvBuilder::new().args(args).build();
+    if type_errors(&ast, &env.borrow()) {
+        return Ok(1);
+    }
+
+    match Env::eval(env, &ast) {
+        Ok(Type::Nil) => Ok(0),
+        Ok(value) => {
+            if let Input::Inline(_) = input {
+                writeln!(out, "{}", value)?;
+            }
+            Ok(0)
+        }
+        Err(err) => {
+            eprintln!("runtime error: {}", err);
+            Ok(1)
+        }
+    }
+}
+
+/// Parses the program of `input`, reporting the warnings, or the syntax
+/// error that stopped it.
+fn parse(input: &Input) -> Option<AstNode> {
+    let source = input.read();
+    let mut parser = Parser::new(&source);
+    let ast = parser.try_parse();
+    for warning in parser.warnings() {
+        eprintln!("{}: {}", input.name(), warning);
+    }
+    ast.map_err(|err| eprintln!("{}: {}", input.name(), err))
+        .ok()
+}
+
+/// Reports the type errors of `ast` as a program evaluated in `env`,
+/// returning whether there were any.
+fn type_errors(ast: &AstNode, env: &Env) -> bool {
+    let errors = checker::check_in(ast, env);
+    for error in &errors {
+        eprintln!("{}", error);
+    }
+    !errors.is_empty()
+}
+
+fn check(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut inputs = vec![Input::from_args(&mut args)];
+    let mut args = args.peekable();
+    while args.peek().is_some() {
+        inputs.push(Input::from_args(&mut args));
+    }
+
+    let mut status = 0;
+    for input in &inputs {
+        if parse(input).is_none() {
+            status = 1;
+        }
+    }
+    status
+}
+
+fn fmt(out: &mut impl Write, args: impl Iterator<Item = String>) -> io::Result<i32> {
+    let mut check = false;
+    let mut paths = vec![];
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            "-" => {}
+            flag if flag.starts_with('-') => usage_error(&format!("unknown option {}", flag)),
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.is_empty() {
+        let source = Input::Stdin.read();
+        return match formatter::format(&source) {
+            Ok(formatted) if check && formatted != source => {
+                eprintln!("<stdin> is not formatted");
+                Ok(1)
+            }
+            Ok(_) if check => Ok(0),
+            Ok(formatted) => write!(out, "{}", formatted).map(|()| 0),
+            Err(err) => {
+                eprintln!("<stdin>: {}", err);
+                Ok(1)
+            }
+        };
+    }
+
+    let mut status = 0;
+    for path in &paths {
+        let source = Input::File(path.clone()).read();
+        match formatter::format(&source) {
+            Ok(formatted) if formatted == source => {}
+            Ok(_) if check => {
+                writeln!(out, "{} is not formatted", path)?;
+                status = 1;
+            }
+            Ok(formatted) => {
+                if let Err(err) = fs::write(path, formatted) {
+                    eprintln!("{}: {}", path, err);
+                    return Ok(2);
+                }
+            }
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                status = 1;
+            }
+        }
+    }
+    Ok(status)
+}
+
+fn ast(out: &mut impl Write, mut args: impl Iterator<Item = String>) -> io::Result<i32> {
+    let mut args = args.by_ref().peekable();
+    let format = match args.peek().and_then(|a| a.strip_prefix("--format=")) {
+        Some(format) => {
+            let format = format.to_owned();
+            args.next();
+            format
+        }
+        None => "sexpr".to_owned(),
+    };
+    if !["sexpr", "json", "debug"].contains(&format.as_str()) {
+        usage_error(&format!("unknown AST format {}", format));
+    }
+
+    let ast = match parse(&Input::from_args(&mut args)) {
+        Some(ast) => ast,
+        None => return Ok(1),
+    };
+    match format.as_str() {
+        "sexpr" => writeln!(out, "{}", ast.to_sexpr())?,
+        "json" => writeln!(out, "{}", ast.to_json())?,
+        _ => writeln!(out, "{:#?}", ast)?,
+    }
+    Ok(0)
+}
+
+fn tokens(out: &mut impl Write, input: Input) -> io::Result<i32> {
+    for token in Lexer::new(&input.read()) {
+        writeln!(out, "{}-{} {:?}", token.position, token.end, token.kind)?;
+    }
+    Ok(0)
+}
+
+/// Runs every program found in `paths`, and reports which of them failed,
+/// or printed something else than their `.out` file holds.
+fn test(out: &mut impl Write, paths: Vec<String>) -> io::Result<i32> {
+    let paths = if paths.is_empty() {
+        vec![".".to_owned()]
+    } else {
+        paths
+    };
+
+    let mut programs = vec![];
+    for path in &paths {
+        if let Err(err) = find_programs(Path::new(path), &mut programs) {
+            eprintln!("{}: {}", path, err);
+            return Ok(2);
+        }
+    }
+
+    let mut failed = 0;
+    for program in &programs {
+        match test_program(program) {
+            Ok(()) => writeln!(out, "test {} ... ok", program.display())?,
+            Err(failure) => {
+                writeln!(out, "test {} ... FAILED\n{}", program.display(), failure)?;
+                failed += 1;
+            }
+        }
+    }
+
+    writeln!(
+        out,
+        "\n{} passed, {} failed",
+        programs.len() - failed,
+        failed
+    )?;
+    Ok((failed > 0) as i32)
+}
+
+/// Collects the `.kotoba` files at `path`, looking into directories.
+fn find_programs(path: &Path, programs: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        programs.push(path.to_owned());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() || entry.extension() == Some("kotoba".as_ref()) {
+            find_programs(&entry, programs)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `program`, returning why it failed, if it did.
+fn test_program(program: &Path) -> Result<(), String> {
+    let source = fs::read_to_string(program).map_err(|err| err.to_string())?;
+    let ast = Parser::new(&source)
+        .try_parse()
+        .map_err(|err| err.to_string())?;
+    // a test can't exit the runner or wait for input
+    let output = Output::default();
+    let env = EnvBuilder::new()
+        .capabilities(Capabilities {
+            stdin: false,
+            process: false,
+            ..Capabilities::default()
+        })
+        .output(output.clone())
+        .build();
+    if let Some(error) = checker::check_in(&ast, &env.borrow()).first() {
+        return Err(error.to_string());
+    }
+    if let Err(err) = Env::eval(env, &ast) {
+        return Err(format!("runtime error: {}", err));
+    }
+
+    let printed = String::from_utf8_lossy(&output.0.borrow()).into_owned();
+    match fs::read_to_string(program.with_extension("out")) {
+        Ok(expected) if expected != printed => Err(format!(
+            "expected output:\n{}\nactual output:\n{}",
+            expected, printed
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// What a program prints while tested.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
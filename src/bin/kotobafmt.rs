@@ -0,0 +1,85 @@
+use kotoba::formatter;
+use std::{
+    env, fs,
+    io::{self, Read},
+    process,
+};
+
+const USAGE: &str = "usage: kotobafmt [--check] [FILE...]
+
+Formats the given files in place, or standard input to standard output.
+With --check, nothing is written and the exit status is 1 if any input
+is not formatted.";
+
+fn main() {
+    let mut check = false;
+    let mut paths = vec![];
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--check" => check = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            flag if flag.starts_with("--") => {
+                eprintln!("unknown option {}\n{}", flag, USAGE);
+                process::exit(2);
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.is_empty() {
+        let mut source = String::new();
+        if let Err(err) = io::stdin().read_to_string(&mut source) {
+            eprintln!("<stdin>: {}", err);
+            process::exit(2);
+        }
+
+        match formatter::format(&source) {
+            Ok(formatted) if check => {
+                if formatted != source {
+                    eprintln!("<stdin> is not formatted");
+                    process::exit(1);
+                }
+            }
+            Ok(formatted) => print!("{}", formatted),
+            Err(err) => {
+                eprintln!("<stdin>: {}", err);
+                process::exit(2);
+            }
+        }
+        return;
+    }
+
+    let mut status = 0;
+    for path in &paths {
+        match format_file(path, check) {
+            Ok(formatted) if formatted || !check => {}
+            Ok(_) => {
+                println!("{} is not formatted", path);
+                status = status.max(1);
+            }
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                status = 2;
+            }
+        }
+    }
+    process::exit(status);
+}
+
+/// Formats the file at `path`, unless only checking. Returns whether it was
+/// already formatted.
+fn format_file(path: &str, check: bool) -> Result<bool, String> {
+    let source = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let formatted = formatter::format(&source).map_err(|err| err.to_string())?;
+
+    if formatted == source {
+        return Ok(true);
+    }
+    if !check {
+        fs::write(path, formatted).map_err(|err| err.to_string())?;
+    }
+    Ok(false)
+}
@@ -0,0 +1,96 @@
+use kotoba::{
+    lint::{self, Config},
+    parser::Parser,
+};
+use std::{env, fs, path::Path, process};
+
+const USAGE: &str = "usage: lint [--config FILE] FILE...
+
+Reports likely mistakes in kotoba programs. Rules are configured in FILE,
+or else in kotoba-lint.toml if there is one in the current directory.
+The exit status is 1 if anything was reported.";
+
+/// The configuration used when none is given.
+const DEFAULT_CONFIG: &str = "kotoba-lint.toml";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut config_path = None;
+    let mut paths = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => match args.next() {
+                Some(path) => config_path = Some(path),
+                None => usage_error("--config needs a file"),
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            flag if flag.starts_with("--") => usage_error(&format!("unknown option {}", flag)),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        usage_error("no files given");
+    }
+
+    let config = match config_path {
+        Some(path) => read_config(&path),
+        None if Path::new(DEFAULT_CONFIG).exists() => read_config(DEFAULT_CONFIG),
+        None => Config::default(),
+    };
+
+    let mut status = 0;
+    for path in &paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                status = 2;
+                continue;
+            }
+        };
+
+        let ast = match Parser::new(&source).try_parse() {
+            Ok(ast) => ast,
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                status = 2;
+                continue;
+            }
+        };
+
+        for lint in lint::lint(&source, &ast, &config) {
+            let start = lint.span.start;
+            println!(
+                "{}:{}:{}: {} [{}]",
+                path,
+                start.line,
+                start.character,
+                lint.message,
+                lint.rule.name()
+            );
+            status = status.max(1);
+        }
+    }
+    process::exit(status);
+}
+
+fn read_config(path: &str) -> Config {
+    let parsed = fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| Config::parse(&text));
+    match parsed {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            process::exit(2);
+        }
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
@@ -0,0 +1,77 @@
+//! A language server for kotoba, speaking the Language Server Protocol over
+//! standard input and output.
+
+mod server;
+
+use self::server::Server;
+use serde_json::Value;
+use std::{
+    io::{self, BufRead, Write},
+    process,
+};
+
+fn main() {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+
+    let mut server = Server::default();
+    loop {
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(err) => {
+                eprintln!("lsp: {}", err);
+                process::exit(1);
+            }
+        };
+
+        for reply in server.handle(&message) {
+            if let Err(err) = write_message(&mut output, &reply) {
+                eprintln!("lsp: {}", err);
+                process::exit(1);
+            }
+        }
+        if let Some(code) = server.exit_code() {
+            process::exit(code);
+        }
+    }
+
+    // the client went away without asking us to exit
+    process::exit(1);
+}
+
+/// Reads a message framed by a `Content-Length` header, or returns `None` at
+/// the end of the input. Messages that aren't valid JSON are read as `null`.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+
+    Ok(Some(serde_json::from_slice(&body).unwrap_or(Value::Null)))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
@@ -0,0 +1,399 @@
+use kotoba::{
+    analysis::{Analysis, SymbolKind},
+    checker::{self, TypeError},
+    lexer::Position,
+    parser::{Parser, Span, Warning},
+    runtime::{builtin_constants, builtin_functions},
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_REQUEST: i32 = -32600;
+const PARSE_ERROR: i32 = -32700;
+
+/// Severities of diagnostics.
+const ERROR: usize = 1;
+const WARNING: usize = 2;
+
+/// Kinds of symbols and completion items.
+const FUNCTION_SYMBOL: usize = 12;
+const FUNCTION_ITEM: usize = 3;
+const VARIABLE_ITEM: usize = 6;
+const CONSTANT_ITEM: usize = 21;
+
+#[derive(Default)]
+pub struct Server {
+    /// The text of the open documents, by URI.
+    documents: HashMap<String, String>,
+    shutdown: bool,
+    exit: bool,
+}
+
+/// What's known about a document that parsed.
+struct Parsed {
+    warnings: Vec<Warning>,
+    type_errors: Vec<TypeError>,
+    analysis: Analysis,
+}
+
+impl Server {
+    /// Returns the exit code once the client asked the server to exit: 0 if
+    /// it asked for a shutdown first, as it should.
+    pub fn exit_code(&self) -> Option<i32> {
+        match (self.exit, self.shutdown) {
+            (false, _) => None,
+            (true, true) => Some(0),
+            (true, false) => Some(1),
+        }
+    }
+
+    /// Handles a request or a notification, returning the messages to send
+    /// back to the client.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = match message["method"].as_str() {
+            Some(method) => method,
+            None if message.is_null() => {
+                return vec![error(Value::Null, PARSE_ERROR, "invalid JSON")]
+            }
+            // a response to a request of ours, which we never make
+            None => return vec![],
+        };
+        let id = message["id"].clone();
+        let params = &message["params"];
+
+        if !id.is_null() {
+            if self.shutdown {
+                return vec![error(id, INVALID_REQUEST, "the server is shut down")];
+            }
+            let result = match method {
+                "initialize" => Some(capabilities()),
+                "shutdown" => {
+                    self.shutdown = true;
+                    Some(Value::Null)
+                }
+                "textDocument/definition" => Some(self.definition(params)),
+                "textDocument/hover" => Some(self.hover(params)),
+                "textDocument/documentSymbol" => Some(self.document_symbols(params)),
+                "textDocument/completion" => Some(self.completion(params)),
+                _ => None,
+            };
+
+            return vec![match result {
+                Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                None => error(id, METHOD_NOT_FOUND, &format!("unknown method {}", method)),
+            }];
+        }
+
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        match method {
+            "exit" => {
+                self.exit = true;
+                vec![]
+            }
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+                self.documents.insert(uri.to_owned(), text.to_owned());
+                vec![publish_diagnostics(uri, diagnostics(text))]
+            }
+            "textDocument/didChange" => {
+                // the whole text is sent on every change
+                let changes = params["contentChanges"].as_array();
+                match changes.and_then(|c| c.last()?["text"].as_str()) {
+                    Some(text) => {
+                        self.documents.insert(uri.to_owned(), text.to_owned());
+                        vec![publish_diagnostics(uri, diagnostics(text))]
+                    }
+                    None => vec![],
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                vec![publish_diagnostics(uri, vec![])]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Parses the document a request is about, along with its text and the
+    /// position the request is about.
+    fn document(&self, params: &Value) -> Option<(&str, &str, Parsed, Position)> {
+        let (uri, text) = self
+            .documents
+            .get_key_value(params["textDocument"]["uri"].as_str()?)?;
+        let parsed = parse(text).ok()?;
+        let position = from_lsp_position(text, &params["position"]);
+        Some((uri, text, parsed, position))
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let (uri, text, parsed, position) = match self.document(params) {
+            Some(document) => document,
+            None => return Value::Null,
+        };
+
+        let symbol = parsed
+            .analysis
+            .reference_at(position)
+            .and_then(|r| r.symbol)
+            .map(|index| &parsed.analysis.symbols[index]);
+        match symbol {
+            Some(symbol) => json!({
+                "uri": uri,
+                "range": name_range(text, symbol.position, &symbol.name),
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let (_, text, parsed, position) = match self.document(params) {
+            Some(document) => document,
+            None => return Value::Null,
+        };
+        let reference = match parsed.analysis.reference_at(position) {
+            Some(reference) => reference,
+            None => return Value::Null,
+        };
+
+        let contents = match reference.symbol {
+            Some(index) => format!("```kotoba\n{}\n```", parsed.analysis.symbols[index].detail),
+            None if reference.function && builtin_functions().contains(&reference.name) => {
+                format!("`{}`: builtin function", reference.name)
+            }
+            None if !reference.function && builtin_constants().contains(&reference.name) => {
+                format!("`{}`: builtin constant", reference.name)
+            }
+            None => return Value::Null,
+        };
+
+        json!({
+            "contents": { "kind": "markdown", "value": contents },
+            "range": name_range(text, reference.position, &reference.name),
+        })
+    }
+
+    fn document_symbols(&self, params: &Value) -> Value {
+        match self.document(params) {
+            Some((_, text, parsed, _)) => function_symbols(text, &parsed.analysis, None).into(),
+            None => json!([]),
+        }
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let item = |label: &str, kind: usize, detail: &str| {
+            json!({
+                "label": label,
+                "kind": kind,
+                "detail": detail,
+            })
+        };
+
+        let mut items = vec![];
+        let mut labels = vec![];
+        if let Some((_, _, parsed, _)) = self.document(params) {
+            for symbol in &parsed.analysis.symbols {
+                if labels.contains(&symbol.name) {
+                    continue;
+                }
+                let kind = match symbol.kind {
+                    SymbolKind::Function => FUNCTION_ITEM,
+                    _ => VARIABLE_ITEM,
+                };
+                items.push(item(&symbol.name, kind, &symbol.detail));
+                labels.push(symbol.name.clone());
+            }
+        }
+
+        for name in builtin_functions() {
+            if !labels.contains(&name) {
+                items.push(item(&name, FUNCTION_ITEM, "builtin function"));
+            }
+        }
+        for name in builtin_constants() {
+            if !labels.contains(&name) {
+                items.push(item(&name, CONSTANT_ITEM, "builtin constant"));
+            }
+        }
+        items.into()
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "capabilities": {
+            // the whole document is sent on every change
+            "textDocumentSync": 1,
+            "definitionProvider": true,
+            "hoverProvider": true,
+            "documentSymbolProvider": true,
+            "completionProvider": {},
+        },
+        "serverInfo": { "name": "kotoba", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn error(id: Value, code: i32, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// Parses and analyzes a document, or returns the syntax error that stopped
+/// it as a diagnostic.
+fn parse(text: &str) -> Result<Parsed, Value> {
+    let mut parser = Parser::new(text);
+    let ast = parser
+        .try_parse()
+        .map_err(|err| diagnostic(text, err.position, 1, ERROR, &err.message))?;
+    let warnings = parser.warnings().to_vec();
+
+    let analysis = Analysis::new(text, &ast);
+    let type_errors = checker::check(&ast);
+    Ok(Parsed {
+        warnings,
+        type_errors,
+        analysis,
+    })
+}
+
+fn diagnostics(text: &str) -> Vec<Value> {
+    let parsed = match parse(text) {
+        Ok(parsed) => parsed,
+        Err(diagnostic) => return vec![diagnostic],
+    };
+
+    let type_errors = parsed
+        .type_errors
+        .iter()
+        .map(|error| span_diagnostic(text, error.span, ERROR, &error.message));
+    let warnings = parsed.warnings.iter().map(|warning| match warning {
+        Warning::NonExhaustiveMatch { token, missing } => {
+            let missing = missing
+                .iter()
+                .map(|p| format!("`{}`", p))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!("non-exhaustive match: {} not covered", missing);
+            diagnostic(text, token.position, "match".len(), WARNING, &message)
+        }
+    });
+    type_errors.chain(warnings).collect()
+}
+
+fn diagnostic(
+    text: &str,
+    position: Position,
+    length: usize,
+    severity: usize,
+    message: &str,
+) -> Value {
+    let end = Position {
+        line: position.line,
+        character: position.character + length,
+    };
+    let span = Span {
+        start: position,
+        end,
+    };
+    span_diagnostic(text, span, severity, message)
+}
+
+fn span_diagnostic(text: &str, span: Span, severity: usize, message: &str) -> Value {
+    json!({
+        "range": range(text, span),
+        "severity": severity,
+        "source": "kotoba",
+        "message": message,
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// Lists the functions defined in `parent`, or at the top level, with the
+/// functions they define nested in them.
+fn function_symbols(text: &str, analysis: &Analysis, parent: Option<usize>) -> Vec<Value> {
+    analysis
+        .symbols
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.kind == SymbolKind::Function && s.parent == parent)
+        .map(|(index, symbol)| {
+            json!({
+                "name": symbol.name,
+                "detail": symbol.detail,
+                "kind": FUNCTION_SYMBOL,
+                "range": range(text, symbol.span),
+                "selectionRange": name_range(text, symbol.position, &symbol.name),
+                "children": function_symbols(text, analysis, Some(index)),
+            })
+        })
+        .collect()
+}
+
+/// Converts a span of `text` into a range.
+fn range(text: &str, span: Span) -> Value {
+    json!({
+        "start": to_lsp_position(text, span.start),
+        "end": to_lsp_position(text, span.end),
+    })
+}
+
+fn name_range(text: &str, position: Position, name: &str) -> Value {
+    let end = Position {
+        line: position.line,
+        character: position.character + name.len(),
+    };
+    range(
+        text,
+        Span {
+            start: position,
+            end,
+        },
+    )
+}
+
+/// Converts a position in `text` into a position of the protocol, whose
+/// lines count from 0, and characters from 0 in UTF-16 code units rather
+/// than in bytes.
+fn to_lsp_position(text: &str, position: Position) -> Value {
+    let line = text.split('\n').nth(position.line - 1).unwrap_or("");
+    let column = position.character - 1;
+    // an error at the end of the input ends past the end of the line
+    let (before, past_end) = match line.get(..column) {
+        Some(before) => (before, 0),
+        None => (line, column.saturating_sub(line.len())),
+    };
+    json!({
+        "line": position.line - 1,
+        "character": before.encode_utf16().count() + past_end,
+    })
+}
+
+/// Converts a position of the protocol into a position in `text`.
+fn from_lsp_position(text: &str, position: &Value) -> Position {
+    let line_index = position["line"].as_u64().unwrap_or(0) as usize;
+    let units = position["character"].as_u64().unwrap_or(0) as usize;
+    let line = text.split('\n').nth(line_index).unwrap_or("");
+
+    let mut counted = 0;
+    let column = line
+        .char_indices()
+        .find(|&(_, c)| {
+            counted += c.len_utf16();
+            counted > units
+        })
+        .map_or(line.len(), |(i, _)| i);
+    Position {
+        line: line_index + 1,
+        character: column + 1,
+    }
+}
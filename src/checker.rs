@@ -0,0 +1,483 @@
+//! Static type checking of the annotations written in a program.
+//!
+//! The checker infers the types of literals, operators and calls to builtins
+//! and to annotated functions, and reports the mismatches it can be sure of,
+//! such as `"a" - 1` or passing a string to a `num` parameter, which would
+//! otherwise only fail once run. Anything it can't tell has type `any`,
+//! which matches every type: unannotated variables and parameters, and calls
+//! to unannotated functions, stay dynamically typed.
+
+use crate::{
+    analysis::bindings_of,
+    parser::*,
+    runtime::{builtin_signature, Env, Signature},
+};
+use std::{collections::HashMap, fmt};
+
+/// A mismatch between the types of a program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "type error at {}: {}", self.span.start, self.message)
+    }
+}
+
+/// Checks `ast`, returning the type errors found in source order.
+///
+/// ```
+/// use kotoba::{checker, parser::Parser};
+///
+/// let ast = Parser::new("fn half(n: num) -> num: n / 2; half(\"4\")").parse();
+/// let errors = checker::check(&ast);
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(
+///     errors[0].to_string(),
+///     "type error at 1:37: expected num for parameter `n` of `half`, found str"
+/// );
+/// ```
+pub fn check(ast: &AstNode) -> Vec<TypeError> {
+    infer(ast, &[]).1
+}
+
+/// Checks `ast` as a program to be evaluated in `env`, returning the type
+/// errors found in source order. Calls to functions the program doesn't
+/// define are checked against those of `env`: its builtins, the native
+/// functions registered with it, and those defined by the programs it has
+/// evaluated.
+///
+/// ```
+/// use kotoba::{checker, parser::Parser, runtime::Env};
+///
+/// let env = Env::new();
+/// env.borrow_mut().register_fn("shout", |s: String| s.to_uppercase());
+///
+/// let ast = Parser::new("shout(1)").parse();
+/// let errors = checker::check_in(&ast, &env.borrow());
+/// assert_eq!(
+///     errors[0].to_string(),
+///     "type error at 1:7: expected str for argument 1 of `shout`, found num"
+/// );
+/// ```
+pub fn check_in(ast: &AstNode, env: &Env) -> Vec<TypeError> {
+    run(ast, &[], &|name| env.signature(name)).1
+}
+
+/// Checks `ast` as if it followed the definition of `variables`, with the
+/// given types, returning the type of the values it evaluates to and the
+/// type errors found in source order.
+///
+/// The program may assign other types to those variables without
+/// `nonlocal`, as it then creates its own.
+///
+/// ```
+/// use kotoba::{checker, parser::*};
+///
+/// let ast = Parser::new("if n > 1: \"many\" else \"one\";").parse();
+/// let (result, errors) = checker::infer(&ast, &[("n", TypeName::Num)]);
+/// assert_eq!(result, TypeName::Str);
+/// assert!(errors.is_empty());
+/// ```
+pub fn infer(ast: &AstNode, variables: &[(&str, TypeName)]) -> (TypeName, Vec<TypeError>) {
+    run(ast, variables, &builtin_signature)
+}
+
+/// Infers the type of `ast` like `infer`, as a program to be evaluated in
+/// `env`, whose functions the calls it doesn't define itself are checked
+/// against, as with `check_in`.
+///
+/// ```
+/// use kotoba::{checker, parser::*, runtime::Env};
+///
+/// let env = Env::new();
+/// let script = Parser::new("fn greet(name: str) -> str: \"hi, {name}\";").parse();
+/// Env::eval(env.clone(), &script).unwrap();
+///
+/// let ast = Parser::new("greet(who)").parse();
+/// let (result, errors) = checker::infer_in(&ast, &[("who", TypeName::Str)], &env.borrow());
+/// assert_eq!(result, TypeName::Str);
+/// assert!(errors.is_empty());
+/// ```
+pub fn infer_in(
+    ast: &AstNode,
+    variables: &[(&str, TypeName)],
+    env: &Env,
+) -> (TypeName, Vec<TypeError>) {
+    run(ast, variables, &|name| env.signature(name))
+}
+
+/// Infers the type of `ast` and checks it, with `globals` returning the
+/// signatures of the functions it doesn't define.
+fn run(
+    ast: &AstNode,
+    variables: &[(&str, TypeName)],
+    globals: &dyn Fn(&str) -> Option<Signature>,
+) -> (TypeName, Vec<TypeError>) {
+    let outer = Scope {
+        variables: variables
+            .iter()
+            .map(|&(name, t)| (name.to_owned(), t))
+            .collect(),
+        functions: HashMap::new(),
+    };
+    let mut checker = Checker {
+        scopes: vec![outer, Scope::default()],
+        globals,
+        function: None,
+        returns_early: false,
+        errors: vec![],
+    };
+    let result = checker.infer(ast);
+
+    let mut errors = checker.errors;
+    errors.sort_by_key(|e| (e.span.start.line, e.span.start.character));
+    (result, errors)
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    variables: HashMap<String, TypeName>,
+    functions: HashMap<String, Signature>,
+}
+
+struct Checker<'a> {
+    scopes: Vec<Scope>,
+    globals: &'a dyn Fn(&str) -> Option<Signature>,
+    /// The name and declared result type of the function being checked.
+    function: Option<(String, TypeName)>,
+    /// Whether the function being checked has a `ret` so far.
+    returns_early: bool,
+    errors: Vec<TypeError>,
+}
+
+impl Checker<'_> {
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(TypeError { message, span });
+    }
+
+    /// Reports `found` unless it matches `expected`, which is described by
+    /// `what`, as in "expected num for `x`, found str".
+    fn expect(&mut self, expected: TypeName, found: TypeName, span: Span, what: &str) {
+        if !matches(expected, found) {
+            let message = format!("expected {} for {}, found {}", expected, what, found);
+            self.error(span, message);
+        }
+    }
+
+    fn variable(&self, name: &str) -> Option<TypeName> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|s| s.variables.get(name).copied())
+    }
+
+    fn function(&self, name: &str) -> Option<Signature> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|s| s.functions.get(name).cloned())
+            .or_else(|| (self.globals)(name))
+    }
+
+    /// Returns the type of the values `node` evaluates to, checking it along
+    /// the way.
+    fn infer(&mut self, node: &AstNode) -> TypeName {
+        match &node.kind {
+            NodeKind::ProgramRoot(stmts) => self.program(stmts),
+            NodeKind::Program(stmts) => {
+                self.scopes.push(Scope::default());
+                let result = self.program(stmts);
+                self.scopes.pop();
+                result
+            }
+            NodeKind::Number(_) => TypeName::Num,
+            NodeKind::Boolean(_) => TypeName::Bool,
+            NodeKind::StringLiteral(_) => TypeName::Str,
+            NodeKind::Interpolation(nodes) => {
+                for n in nodes {
+                    self.infer(n);
+                }
+                TypeName::Str
+            }
+            NodeKind::Identifier(name) => self.variable(name).unwrap_or(TypeName::Any),
+            NodeKind::Nil => TypeName::Nil,
+            NodeKind::Grouping(expr) => self.infer(expr),
+            NodeKind::List(items) => {
+                for i in items {
+                    self.infer(i);
+                }
+                TypeName::List
+            }
+            NodeKind::FnCall {
+                identifier,
+                args,
+                named,
+            } => self.call(identifier, args, named),
+            NodeKind::MethodCall { receiver, args, .. } => {
+                self.infer(receiver);
+                for a in args {
+                    self.infer(a);
+                }
+                TypeName::Any
+            }
+            NodeKind::RetStmt(expr) => {
+                self.returns_early = true;
+                let found = self.infer(expr);
+                if let Some((name, returns)) = self.function.clone() {
+                    let what = format!("the result of `{}`", name);
+                    self.expect(returns, found, expr.span, &what);
+                }
+                // the statements after it are never run
+                TypeName::Any
+            }
+            NodeKind::UnaryExpr { operator, operand } => {
+                let found = self.infer(operand);
+                let expected = match operator {
+                    Op::Bang => TypeName::Bool,
+                    _ => TypeName::Num,
+                };
+                if !matches(expected, found) {
+                    let message = format!("`{}` can't be applied to {}", operator, found);
+                    self.error(node.span, message);
+                }
+                expected
+            }
+            NodeKind::BinaryExpr { operator, lhs, rhs } => {
+                let (lhs, rhs) = (self.infer(lhs), self.infer(rhs));
+                self.binary(node, *operator, lhs, rhs)
+            }
+            NodeKind::Assignment {
+                identifier,
+                operand,
+                nonlocal,
+                annotation,
+            } => {
+                let found = self.infer(operand);
+                let what = format!("`{}`", identifier);
+                let scope = self.scopes.last_mut().unwrap();
+                match annotation {
+                    Some(annotation) => {
+                        scope.variables.insert(identifier.clone(), *annotation);
+                        self.expect(*annotation, found, operand.span, &what);
+                    }
+                    None => {
+                        let declared = if *nonlocal {
+                            self.variable(identifier)
+                        } else {
+                            Some(
+                                *scope
+                                    .variables
+                                    .entry(identifier.clone())
+                                    .or_insert(TypeName::Any),
+                            )
+                        };
+                        if let Some(declared) = declared {
+                            self.expect(declared, found, operand.span, &what);
+                        }
+                    }
+                }
+                TypeName::Nil
+            }
+            NodeKind::IfStmt {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.condition(condition);
+                let then_type = self.infer(then_body);
+                let else_type = match else_body {
+                    Some(else_body) => self.infer(else_body),
+                    None => TypeName::Nil,
+                };
+                join(then_type, else_type)
+            }
+            NodeKind::WhileStmt { condition, body } => {
+                self.condition(condition);
+                self.infer(body);
+                // a loop on `true` only ever ends through a `ret`
+                match condition.kind {
+                    NodeKind::Boolean(true) => TypeName::Any,
+                    _ => TypeName::Nil,
+                }
+            }
+            NodeKind::FnStmt {
+                identifier,
+                params,
+                returns,
+                body,
+            } => {
+                self.function_stmt(identifier, params, *returns, body);
+                TypeName::Nil
+            }
+            NodeKind::MatchStmt { scrutinee, arms } => {
+                self.infer(scrutinee);
+                let mut result = None;
+                for arm in arms {
+                    let mut bindings = vec![];
+                    bindings_of(&arm.pattern, &mut bindings);
+                    self.scopes.push(Scope::default());
+                    for binding in bindings {
+                        let variables = &mut self.scopes.last_mut().unwrap().variables;
+                        variables.insert(binding.to_owned(), TypeName::Any);
+                    }
+                    if let Some(guard) = &arm.guard {
+                        self.infer(guard);
+                    }
+                    let arm_type = self.infer(&arm.body);
+                    self.scopes.pop();
+                    result = Some(result.map_or(arm_type, |r| join(r, arm_type)));
+                }
+                // unless an arm matches anything, the result may be nil
+                let exhaustive = arms.iter().any(|a| {
+                    a.guard.is_none()
+                        && matches!(a.pattern, Pattern::Wildcard | Pattern::Binding(_))
+                });
+                match result {
+                    Some(result) if exhaustive => result,
+                    _ => TypeName::Any,
+                }
+            }
+        }
+    }
+
+    /// Checks the statements of a block, returning the type of the last one.
+    fn program(&mut self, stmts: &[AstNode]) -> TypeName {
+        let mut result = TypeName::Nil;
+        for stmt in stmts {
+            result = self.infer(stmt);
+        }
+        result
+    }
+
+    fn condition(&mut self, condition: &AstNode) {
+        let found = self.infer(condition);
+        self.expect(TypeName::Bool, found, condition.span, "the condition");
+    }
+
+    fn call(
+        &mut self,
+        identifier: &str,
+        args: &[AstNode],
+        named: &[(String, AstNode)],
+    ) -> TypeName {
+        let signature = self.function(identifier);
+
+        for (i, arg) in args.iter().enumerate() {
+            let found = self.infer(arg);
+            let expected = signature.as_ref().and_then(|s| match s.params.get(i) {
+                Some((name, t)) if name.is_empty() => {
+                    Some((*t, format!("argument {} of `{}`", i + 1, identifier)))
+                }
+                Some((name, t)) => Some((*t, format!("parameter `{}` of `{}`", name, identifier))),
+                None => s
+                    .rest
+                    .map(|t| (t, format!("argument {} of `{}`", i + 1, identifier))),
+            });
+            if let Some((expected, what)) = expected {
+                self.expect(expected, found, arg.span, &what);
+            }
+        }
+
+        for (name, arg) in named {
+            let found = self.infer(arg);
+            let expected = signature
+                .as_ref()
+                .and_then(|s| s.params.iter().find(|(n, _)| n == name));
+            if let Some((_, expected)) = expected {
+                let what = format!("parameter `{}` of `{}`", name, identifier);
+                self.expect(*expected, found, arg.span, &what);
+            }
+        }
+
+        signature.map_or(TypeName::Any, |s| s.returns)
+    }
+
+    fn binary(&mut self, node: &AstNode, operator: Op, lhs: TypeName, rhs: TypeName) -> TypeName {
+        use TypeName::*;
+        let (operands, result): (&[TypeName], TypeName) = match operator {
+            Op::EqualEqual | Op::BangEqual => return Bool,
+            Op::And | Op::Or => (&[Bool], Bool),
+            Op::Plus => (&[Num, Str], if lhs == Any { rhs } else { lhs }),
+            Op::Greater | Op::GreaterEqual | Op::Less | Op::LessEqual => (&[Num, Str], Bool),
+            _ => (&[Num], Num),
+        };
+
+        let fits = |t: TypeName| t == Any || operands.contains(&t);
+        if !fits(lhs) || !fits(rhs) || !matches(lhs, rhs) {
+            let message = match (lhs, rhs) {
+                (Any, t) | (t, Any) => format!("`{}` can't be applied to {}", operator, t),
+                _ => format!("`{}` can't be applied to {} and {}", operator, lhs, rhs),
+            };
+            self.error(node.span, message);
+            return if operator == Op::Plus { Any } else { result };
+        }
+        result
+    }
+
+    fn function_stmt(
+        &mut self,
+        identifier: &str,
+        params: &[Param],
+        returns: Option<TypeName>,
+        body: &AstNode,
+    ) {
+        let signature = Signature::of_params(params, returns);
+        let scope = self.scopes.last_mut().unwrap();
+        scope.functions.insert(identifier.to_owned(), signature);
+
+        let returns = returns.unwrap_or(TypeName::Any);
+        let enclosing = self.function.replace((identifier.to_owned(), returns));
+        let enclosing_returns_early = std::mem::replace(&mut self.returns_early, false);
+        self.scopes.push(Scope::default());
+        for param in params {
+            let annotation = param.annotation.unwrap_or(TypeName::Any);
+            if let Some(default) = &param.default {
+                let found = self.infer(default);
+                let what = format!("parameter `{}` of `{}`", param.name, identifier);
+                self.expect(annotation, found, default.span, &what);
+            }
+            let variable = if param.rest {
+                TypeName::List
+            } else {
+                annotation
+            };
+            let variables = &mut self.scopes.last_mut().unwrap().variables;
+            variables.insert(param.name.clone(), variable);
+        }
+
+        // parameters live in the same scope as the body
+        let (found, last) = match &body.kind {
+            NodeKind::Program(stmts) => (self.program(stmts), stmts.last()),
+            _ => (self.infer(body), None),
+        };
+        // the `ret`s are checked on their own, and may keep the end of the
+        // body from ever being reached
+        if !self.returns_early {
+            let what = format!("the result of `{}`", identifier);
+            let span = last.map_or(body.span, |l| l.span);
+            self.expect(returns, found, span, &what);
+        }
+
+        self.scopes.pop();
+        self.function = enclosing;
+        self.returns_early = enclosing_returns_early;
+    }
+}
+
+/// Returns whether a value of type `found` may be used where `expected` is.
+fn matches(expected: TypeName, found: TypeName) -> bool {
+    expected == TypeName::Any || found == TypeName::Any || expected == found
+}
+
+/// Returns the type of values that may be of type `a` or `b`.
+fn join(a: TypeName, b: TypeName) -> TypeName {
+    if a == b {
+        a
+    } else {
+        TypeName::Any
+    }
+}
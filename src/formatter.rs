@@ -0,0 +1,454 @@
+//! Canonical formatting of kotoba source code.
+//!
+//! Statements are put one per line, separated by `,` unless they end with
+//! the `;` of a block, and the bodies of blocks are indented by four spaces
+//! with the closing `;` on the line of their last statement. A block written
+//! on a single line is kept that way, as long as it fits within the width
+//! and has no comments inside. Comments are kept where they were, either on
+//! lines of their own or after the statement on their line, and so is a
+//! single blank line between statements.
+
+use crate::{
+    lexer::{Comment, Position},
+    parser::*,
+};
+
+const WIDTH: usize = 80;
+const INDENT: &str = "    ";
+
+/// Formats `source`, failing if it does not parse.
+///
+/// ```
+/// use kotoba::formatter;
+///
+/// let formatted = formatter::format("x=1,while x<3 :nonlocal x=x+1 ;// done").unwrap();
+/// assert_eq!(formatted, "x = 1,\nwhile x < 3: nonlocal x = x + 1; // done\n");
+/// ```
+pub fn format(source: &str) -> Result<String, SyntaxError> {
+    let mut parser = Parser::new(source);
+    let ast = parser.try_parse()?;
+
+    let mut printer = Printer::new(parser.comments(), false);
+    if let NodeKind::ProgramRoot(stmts) = &ast.kind {
+        printer.stmts(stmts, false);
+    }
+    Ok(printer.finish())
+}
+
+/// Formats the head of a function declaration, such as `fn f(a, b = 2)`.
+///
+/// ```
+/// use kotoba::{formatter, parser::*};
+///
+/// let ast = Parser::new("fn f(a: num, b = [1,2]): a;").parse();
+/// if let NodeKind::ProgramRoot(stmts) = &ast.kind {
+///     if let NodeKind::FnStmt { identifier, params, .. } = &stmts[0].kind {
+///         assert_eq!(formatter::signature(identifier, params), "fn f(a: num, b = [1, 2])");
+///     }
+/// }
+/// ```
+pub fn signature(identifier: &str, params: &[Param]) -> String {
+    let mut printer = Printer::new(&[], true);
+    printer.signature(identifier, params);
+    printer.out
+}
+
+fn before(a: Position, b: Position) -> bool {
+    (a.line, a.character) < (b.line, b.character)
+}
+
+struct Printer<'c> {
+    out: String,
+    indent: usize,
+    /// Whether nothing was written on the current line yet, not even its
+    /// indentation.
+    line_start: bool,
+    /// The comments yet to be printed.
+    comments: &'c [Comment],
+    /// The comments to be printed at the end of the current line.
+    trailing: Vec<&'c str>,
+    /// The source line of what was printed last, to tell which comments
+    /// follow it on the same line and where blank lines were.
+    last_line: usize,
+    /// Whether to print everything on a single line.
+    flat: bool,
+}
+
+impl<'c> Printer<'c> {
+    fn new(comments: &'c [Comment], flat: bool) -> Self {
+        Printer {
+            out: String::new(),
+            indent: 0,
+            line_start: true,
+            comments,
+            trailing: vec![],
+            last_line: 1,
+            flat,
+        }
+    }
+
+    fn write(&mut self, s: &str) {
+        if self.line_start {
+            self.out.push_str(&INDENT.repeat(self.indent));
+            self.line_start = false;
+        }
+        self.out.push_str(s);
+    }
+
+    /// Ends the current line, along with any comments that were on the
+    /// source line last printed, before whatever is printed next at `next`.
+    fn end_line(&mut self, next: Position) {
+        while let Some(c) = self.comments.first() {
+            if self.line_start || c.position.line != self.last_line || !before(c.position, next) {
+                break;
+            }
+            self.trailing.push(&c.text);
+            self.comments = &self.comments[1..];
+        }
+        self.newline();
+    }
+
+    fn newline(&mut self) {
+        for comment in self.trailing.drain(..) {
+            self.out.push(' ');
+            self.out.push_str(comment);
+        }
+        self.out.push('\n');
+        self.line_start = true;
+    }
+
+    fn column(&self) -> usize {
+        if self.line_start {
+            self.indent * INDENT.len()
+        } else {
+            self.out.len() - self.out.rfind('\n').map_or(0, |i| i + 1)
+        }
+    }
+
+    /// Starts a new line for something that begins at `start` in the source,
+    /// printing the comments that come before it on lines of their own.
+    /// Blank lines are kept, except before the first thing in a block.
+    fn break_before(&mut self, start: Position, first: bool) {
+        if !self.out.is_empty() {
+            self.end_line(start);
+        }
+
+        let mut blank_allowed = !first;
+        while let Some(c) = self.comments.first() {
+            if !before(c.position, start) {
+                break;
+            }
+            self.comments = &self.comments[1..];
+
+            if blank_allowed && c.position.line > self.last_line + 1 {
+                self.newline();
+            }
+            self.write(&c.text);
+            self.last_line = c.position.line;
+            self.newline();
+            blank_allowed = true;
+        }
+
+        if blank_allowed && start.line > self.last_line + 1 {
+            self.newline();
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if !self.out.is_empty() {
+            self.end_line(Position {
+                line: usize::MAX,
+                character: 0,
+            });
+        }
+
+        for c in self.comments {
+            if !self.out.is_empty() && c.position.line > self.last_line + 1 {
+                self.out.push('\n');
+            }
+            self.out.push_str(&c.text);
+            self.out.push('\n');
+            self.last_line = c.position.line;
+        }
+        self.out
+    }
+
+    /// Prints statements on lines of their own, or all on the current line.
+    fn stmts(&mut self, stmts: &[AstNode], inline: bool) {
+        for (i, stmt) in stmts.iter().enumerate() {
+            if !inline {
+                self.break_before(stmt.span.start, i == 0);
+            } else if i > 0 {
+                self.write(" ");
+            }
+
+            self.node(stmt);
+            let ends_with_semicolon = matches!(
+                stmt.kind,
+                NodeKind::WhileStmt { .. } | NodeKind::FnStmt { .. }
+            ) || Parser::ends_with_block(stmt);
+            if i + 1 < stmts.len() && !ends_with_semicolon {
+                self.write(",");
+            }
+            self.last_line = stmt.span.end.line;
+        }
+    }
+
+    fn signature(&mut self, identifier: &str, params: &[Param]) {
+        self.write(&format!("fn {}(", identifier));
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            if param.rest {
+                self.write("*");
+            }
+            self.write(&param.name);
+            if let Some(annotation) = param.annotation {
+                self.write(&format!(": {}", annotation));
+            }
+            if let Some(default) = &param.default {
+                self.write(" = ");
+                self.node(default);
+            }
+        }
+        self.write(")");
+    }
+
+    /// Decides whether a block is printed on a single line.
+    fn inline(&self, node: &AstNode) -> bool {
+        if self.flat {
+            return true;
+        }
+
+        let span = node.span;
+        let has_comments = self
+            .comments
+            .iter()
+            .any(|c| !before(c.position, span.start) && before(c.position, span.end));
+        if span.start.line != span.end.line || has_comments {
+            return false;
+        }
+
+        let mut flat = Printer::new(&[], true);
+        flat.node(node);
+        self.column() + flat.out.len() <= WIDTH
+    }
+
+    fn body(&mut self, body: &AstNode, inline: bool) {
+        let stmts = match &body.kind {
+            NodeKind::Program(stmts) => stmts,
+            _ => unreachable!(),
+        };
+
+        if !inline {
+            self.indent += 1;
+            self.stmts(stmts, false);
+            self.indent -= 1;
+        } else if !stmts.is_empty() {
+            self.write(" ");
+            self.stmts(stmts, true);
+        }
+    }
+
+    fn header(&mut self, keyword: &str, expr: &AstNode) {
+        self.write(keyword);
+        self.write(" ");
+        self.node(expr);
+        self.write(":");
+        self.last_line = expr.span.end.line;
+    }
+
+    fn list(&mut self, nodes: &[AstNode]) {
+        for (i, node) in nodes.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &AstNode) {
+        match &node.kind {
+            NodeKind::ProgramRoot(stmts) | NodeKind::Program(stmts) => self.stmts(stmts, self.flat),
+            NodeKind::Number(n) => self.write(&n.to_string()),
+            NodeKind::Boolean(b) => self.write(&b.to_string()),
+            NodeKind::StringLiteral(s) => self.write(&format!("\"{}\"", s.replace('{', "{{"))),
+            NodeKind::Interpolation(parts) => {
+                self.write("\"");
+                for part in parts {
+                    match &part.kind {
+                        // the literal text has the span of the whole string
+                        NodeKind::StringLiteral(s) if part.span == node.span => {
+                            self.write(&s.replace('{', "{{"))
+                        }
+                        _ => {
+                            self.write("{");
+                            self.node(part);
+                            self.write("}");
+                        }
+                    }
+                }
+                self.write("\"");
+            }
+            NodeKind::Identifier(id) => self.write(id),
+            NodeKind::Nil => self.write("nil"),
+            NodeKind::Grouping(expr) => {
+                self.write("(");
+                self.node(expr);
+                self.write(")");
+            }
+            NodeKind::List(items) => {
+                self.write("[");
+                self.list(items);
+                self.write("]");
+            }
+            NodeKind::FnCall {
+                identifier,
+                args,
+                named,
+            } => {
+                self.write(identifier);
+                self.write("(");
+                self.list(args);
+                for (i, (name, arg)) in named.iter().enumerate() {
+                    if i > 0 || !args.is_empty() {
+                        self.write(", ");
+                    }
+                    self.write(name);
+                    self.write(": ");
+                    self.node(arg);
+                }
+                self.write(")");
+            }
+            NodeKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                self.node(receiver);
+                self.write(".");
+                self.write(method);
+                self.write("(");
+                self.list(args);
+                self.write(")");
+            }
+            NodeKind::RetStmt(expr) => {
+                self.write("ret ");
+                self.node(expr);
+            }
+            NodeKind::UnaryExpr { operator, operand } => {
+                self.write(&operator.to_string());
+                self.node(operand);
+            }
+            NodeKind::BinaryExpr { operator, lhs, rhs } => {
+                self.node(lhs);
+                self.write(&format!(" {} ", operator));
+                self.node(rhs);
+            }
+            NodeKind::Assignment {
+                identifier,
+                operand,
+                nonlocal,
+                annotation,
+            } => {
+                if *nonlocal {
+                    self.write("nonlocal ");
+                }
+                self.write(identifier);
+                if let Some(annotation) = annotation {
+                    self.write(&format!(": {}", annotation));
+                }
+                self.write(" = ");
+                self.node(operand);
+            }
+            NodeKind::IfStmt {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                let inline = self.inline(node);
+                self.header("if", condition);
+                self.body(then_body, inline);
+
+                if let Some(else_body) = else_body {
+                    if inline {
+                        self.write(" else");
+                    } else {
+                        self.end_line(else_body.span.start);
+                        self.write("else");
+                    }
+                    self.body(else_body, inline);
+                }
+                self.write(";");
+            }
+            NodeKind::WhileStmt { condition, body } => {
+                let inline = self.inline(node);
+                self.header("while", condition);
+                self.body(body, inline);
+                self.write(";");
+            }
+            NodeKind::FnStmt {
+                identifier,
+                params,
+                returns,
+                body,
+            } => {
+                let inline = self.inline(node);
+                self.signature(identifier, params);
+                if let Some(returns) = returns {
+                    self.write(&format!(" -> {}", returns));
+                }
+                self.write(":");
+                self.last_line = node.span.start.line;
+                self.body(body, inline);
+                self.write(";");
+            }
+            NodeKind::MatchStmt { scrutinee, arms } => {
+                let inline = self.inline(node);
+                self.header("match", scrutinee);
+
+                if !inline {
+                    self.indent += 1;
+                }
+                for (i, arm) in arms.iter().enumerate() {
+                    if inline {
+                        self.write(" ");
+                    } else {
+                        self.break_before(arm.body.span.start, i == 0);
+                    }
+                    self.arm(arm);
+                    if i + 1 < arms.len() {
+                        self.write(",");
+                    }
+                    self.last_line = arm.body.span.end.line;
+                }
+                if !inline {
+                    self.indent -= 1;
+                }
+                self.write(";");
+            }
+        }
+    }
+
+    fn arm(&mut self, arm: &MatchArm) {
+        self.write(&pattern(&arm.pattern));
+        if let Some(guard) = &arm.guard {
+            self.write(" if ");
+            self.node(guard);
+        }
+        self.write(" => ");
+        self.node(&arm.body);
+    }
+}
+
+fn pattern(p: &Pattern) -> String {
+    match p {
+        Pattern::StringLiteral(s) => format!("\"{}\"", s.replace('{', "{{")),
+        Pattern::List(items) => {
+            let items = items.iter().map(pattern).collect::<Vec<_>>();
+            format!("[{}]", items.join(", "))
+        }
+        other => other.to_string(),
+    }
+}
@@ -1,9 +1,14 @@
+pub use crate::source_stream::Position;
+
 use crate::source_stream::*;
+use std::{fmt, mem};
 
 #[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub position: Position,
+    /// The position right after the last byte of the token.
+    pub end: Position,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -12,10 +17,13 @@ pub enum TokenKind {
     Boolean(bool),
     Identifier(String),
     StringLiteral(String),
+    InterpolatedString(Vec<StringPart>),
     Nil,
 
     OpenParen,
     CloseParen,
+    OpenBracket,
+    CloseBracket,
 
     Equal,
     EqualEqual,
@@ -32,24 +40,135 @@ pub enum TokenKind {
     Plus,
     Minus,
     Star,
+    StarStar,
     Slash,
+    TildeSlash,
     Percent,
 
+    Dot,
     Colon,
     Comma,
     Semicolon,
+    FatArrow,
+    Arrow,
 
     If,
     Else,
     While,
     Fn,
     Ret,
+    Match,
     Nonlocal, // such hack much bodge wow
+
+    /// Source that doesn't make a token, which the parser reports as a
+    /// syntax error.
+    Invalid(LexError),
+}
+
+/// Why a piece of the source doesn't make a token.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    UnrecognizedCharacter(char),
+    UnclosedString,
+    /// A `{` in a string literal without the `}` ending the expression it
+    /// starts. A literal `{` is written as `{{`.
+    UnclosedInterpolation,
+}
+
+impl LexError {
+    /// Returns whether the source ended where more of it was expected, so
+    /// that appending to it may fix the error.
+    pub fn is_incomplete(&self) -> bool {
+        !matches!(self, LexError::UnrecognizedCharacter(_))
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexError::UnrecognizedCharacter(c) => write!(f, "unrecognized character `{}`", c),
+            LexError::UnclosedString => f.write_str("unclosed string literal"),
+            LexError::UnclosedInterpolation => {
+                f.write_str("unclosed `{` in string literal; write `{{` for a literal `{`")
+            }
+        }
+    }
+}
+
+/// Tokens are displayed the way they are written in the source.
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            TokenKind::Number(n) => return write!(f, "{}", n),
+            TokenKind::Boolean(b) => return write!(f, "{}", b),
+            TokenKind::Identifier(id) => id,
+            TokenKind::StringLiteral(s) => return write!(f, "\"{}\"", s.replace('{', "{{")),
+            TokenKind::InterpolatedString(_) => "\"{...}\"",
+            TokenKind::Nil => "nil",
+            TokenKind::OpenParen => "(",
+            TokenKind::CloseParen => ")",
+            TokenKind::OpenBracket => "[",
+            TokenKind::CloseBracket => "]",
+            TokenKind::Equal => "=",
+            TokenKind::EqualEqual => "==",
+            TokenKind::Bang => "!",
+            TokenKind::BangEqual => "!=",
+            TokenKind::Greater => ">",
+            TokenKind::GreaterEqual => ">=",
+            TokenKind::Less => "<",
+            TokenKind::LessEqual => "<=",
+            TokenKind::And => "and",
+            TokenKind::Or => "or",
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Star => "*",
+            TokenKind::StarStar => "**",
+            TokenKind::Slash => "/",
+            TokenKind::TildeSlash => "~/",
+            TokenKind::Percent => "%",
+            TokenKind::Dot => ".",
+            TokenKind::Colon => ":",
+            TokenKind::Comma => ",",
+            TokenKind::Semicolon => ";",
+            TokenKind::FatArrow => "=>",
+            TokenKind::Arrow => "->",
+            TokenKind::If => "if",
+            TokenKind::Else => "else",
+            TokenKind::While => "while",
+            TokenKind::Fn => "fn",
+            TokenKind::Ret => "ret",
+            TokenKind::Match => "match",
+            TokenKind::Nonlocal => "nonlocal",
+            TokenKind::Invalid(err) => return write!(f, "{}", err),
+        };
+        f.write_str(text)
+    }
+}
+
+/// A `//` comment, which the parser skips over but tools such as the
+/// formatter need to keep.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// The text of the comment, from the `//` up to the end of the line,
+    /// without trailing whitespace.
+    pub text: String,
+    pub position: Position,
+}
+
+/// A piece of an interpolated string literal: either verbatim text, or the
+/// source code of an embedded expression along with its position.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Literal(String),
+    Code(String, Position),
 }
 
 pub struct Lexer<'source> {
     source: SourceStream<'source>,
     peek_cache: Option<Token>,
+    last_end: Position,
+    comments: Vec<Comment>,
+    error: Option<Token>,
 }
 
 impl Iterator for Lexer<'_> {
@@ -58,33 +177,61 @@ impl Iterator for Lexer<'_> {
     /// Consumes some source code, yielding an appropriate `Token`.
     /// Returns `None` only when source stream is empty.
     fn next(&mut self) -> Option<Self::Item> {
-        if self.peek_cache.is_some() {
-            return self.peek_cache.take();
-        }
-
-        self.source.take_while(u8::is_ascii_whitespace);
-        let position = self.source.current_position();
+        let token = match self.peek_cache.take() {
+            Some(t) => Some(t),
+            None => self.lex(),
+        };
 
-        self.source.peek().map(|c| match c {
-            b'a'..=b'z' | b'A'..=b'Z' | b'_' => self.handle_identifier(position),
-            b'=' | b'!' | b'>' | b'<' => self.handle_size_2_operator(position),
-            b'0'..=b'9' => self.handle_number(position),
-            b'"' => self.handle_string(position),
-            _ => self.handle_size_1_token(position),
-        })
+        if let Some(t) = &token {
+            self.last_end = t.end;
+        }
+        token
     }
 }
 
 impl<'s> Lexer<'s> {
     /// Initializes a new `Lexer` with the given source code `&str`.
-    /// `source` must be a valid ASCII string.
+    /// Characters other than ASCII ones may only appear in string literals
+    /// and comments.
     pub fn new(source: &'s str) -> Self {
+        let source = SourceStream::new(source);
+        Self {
+            last_end: source.current_position(),
+            source,
+            peek_cache: None,
+            comments: vec![],
+            error: None,
+        }
+    }
+
+    /// Initializes a new `Lexer` over a fragment of a larger source, such as
+    /// an expression embedded in a string literal.
+    pub(crate) fn with_position(source: &'s str, position: Position) -> Self {
         Self {
-            source: SourceStream::new(source),
+            source: SourceStream::with_position(source, position),
             peek_cache: None,
+            last_end: position,
+            comments: vec![],
+            error: None,
         }
     }
 
+    /// Returns the end of the last token consumed by `next()`, or the start
+    /// of the source if there was none.
+    pub fn last_end(&self) -> Position {
+        self.last_end
+    }
+
+    /// Returns the comments skipped over so far, in source order.
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    /// Returns the first `Invalid` token lexed so far, if any.
+    pub fn error(&self) -> Option<&Token> {
+        self.error.as_ref()
+    }
+
     /// Peeks next token in the stream without consuming it.
     ///
     /// Peeking a certain token the first time advances the iterator, all
@@ -92,7 +239,7 @@ impl<'s> Lexer<'s> {
     /// the cached value instead.
     pub fn peek(&mut self) -> Option<Token> {
         if self.peek_cache.is_none() {
-            self.peek_cache = self.next();
+            self.peek_cache = self.lex();
         }
 
         self.peek_cache.clone()
@@ -120,58 +267,95 @@ impl<'s> Lexer<'s> {
         None
     }
 
-    fn handle_size_1_token(&mut self, position: Position) -> Token {
-        let kind = match self.source.next().unwrap() {
+    fn lex(&mut self) -> Option<Token> {
+        self.skip_trivia();
+        let position = self.source.current_position();
+
+        let kind = match self.source.peek()? {
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => self.handle_identifier(),
+            b'=' | b'!' | b'>' | b'<' => self.handle_size_2_operator(),
+            b'0'..=b'9' => self.handle_number(),
+            b'"' => self.handle_string(),
+            c if !c.is_ascii() => {
+                let c = self
+                    .source
+                    .next_char()
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                TokenKind::Invalid(LexError::UnrecognizedCharacter(c))
+            }
+            _ => self.handle_size_1_token(),
+        };
+
+        let token = Token {
+            kind,
+            position,
+            end: self.source.current_position(),
+        };
+        if let (TokenKind::Invalid(_), None) = (&token.kind, &self.error) {
+            self.error = Some(token.clone());
+        }
+        Some(token)
+    }
+
+    /// Consumes whitespace and comments, up to the next token.
+    fn skip_trivia(&mut self) {
+        loop {
+            self.source.take_while(u8::is_ascii_whitespace);
+            if !self.source.starts_with("//") {
+                return;
+            }
+
+            let position = self.source.current_position();
+            let text = self.source.take_while(|&c| c != b'\n');
+            self.comments.push(Comment {
+                text: text.trim_end().to_owned(),
+                position,
+            });
+        }
+    }
+
+    fn handle_size_1_token(&mut self) -> TokenKind {
+        match self.source.next().unwrap() {
             b'+' => TokenKind::Plus,
+            b'-' if self.source.expect(b'>') => TokenKind::Arrow,
             b'-' => TokenKind::Minus,
+            b'*' if self.source.expect(b'*') => TokenKind::StarStar,
             b'*' => TokenKind::Star,
             b'/' => TokenKind::Slash,
+            b'~' if self.source.expect(b'/') => TokenKind::TildeSlash,
             b'%' => TokenKind::Percent,
             b'(' => TokenKind::OpenParen,
             b')' => TokenKind::CloseParen,
+            b'[' => TokenKind::OpenBracket,
+            b']' => TokenKind::CloseBracket,
+            b'.' => TokenKind::Dot,
             b':' => TokenKind::Colon,
             b',' => TokenKind::Comma,
             b';' => TokenKind::Semicolon,
-            other => panic!(
-                "lexical error: unrecognized byte '{}' (0x{:x}) at position {}",
-                other as char, other, position
-            ),
-        };
-
-        Token { kind, position }
+            other => TokenKind::Invalid(LexError::UnrecognizedCharacter(other as char)),
+        }
     }
 
     /// Consumes the bytes that make a number literal, yielding a `Number`
     /// token.
-    fn handle_number(&mut self, position: Position) -> Token {
-        let mut acc = 0.0;
-        
+    fn handle_number(&mut self) -> TokenKind {
         // read whole part
-        while let Some(b'0'...b'9') = self.source.peek() {
-            acc *= 10.0;
-            acc += f64::from(self.source.next().unwrap() - b'0');
-        }
+        let mut literal = self.source.take_while(u8::is_ascii_digit).to_owned();
 
         if self.source.expect(b'.') {
-            let mut fraction = 10.0;
             // ok, read fractional part
-            while let Some(b'0'...b'9') = self.source.peek() {
-                acc += f64::from(self.source.next().unwrap() - b'0') / fraction;
-                fraction *= 10.0;
-            }
+            literal.push('.');
+            literal.push_str(self.source.take_while(u8::is_ascii_digit));
         }
 
-        Token {
-            position,
-            kind: TokenKind::Number(acc),
-        }
+        TokenKind::Number(literal.parse().unwrap())
     }
 
     /// Consumes the bytes that make an identifier, yielding an appropriate
     /// token.
-    fn handle_identifier(&mut self, position: Position) -> Token {
+    fn handle_identifier(&mut self) -> TokenKind {
         let is_ident = |c: &u8| c.is_ascii_alphanumeric() || *c == b'_';
-        let kind = match self.source.take_while(is_ident) {
+        match self.source.take_while(is_ident) {
             "true" => TokenKind::Boolean(true),
             "false" => TokenKind::Boolean(false),
             "nil" => TokenKind::Nil,
@@ -182,33 +366,78 @@ impl<'s> Lexer<'s> {
             "while" => TokenKind::While,
             "fn" => TokenKind::Fn,
             "ret" => TokenKind::Ret,
+            "match" => TokenKind::Match,
             "nonlocal" => TokenKind::Nonlocal,
             other => TokenKind::Identifier(other.to_owned()),
-        };
-
-        Token { position, kind }
+        }
     }
 
     /// Consumes the bytes that make a string literal, yielding a
-    /// `StringLiteral` token. Panics if no closing quote was found.
-    fn handle_string(&mut self, position: Position) -> Token {
+    /// `StringLiteral` token, or an `InterpolatedString` token if the literal
+    /// embeds any `{expressions}`. A literal `{` is written as `{{`.
+    /// Yields an `Invalid` token if the literal isn't closed.
+    fn handle_string(&mut self) -> TokenKind {
         self.source.expect(b'"');
-        let string_contents = self.source.take_while(|c| *c != b'"');
-        if !self.source.expect(b'"') {
-            panic!("unclosed string literal at position {}", position);
+
+        let mut parts = vec![];
+        let mut literal = String::new();
+        loop {
+            literal.push_str(self.source.take_while(|&c| c != b'"' && c != b'{'));
+
+            match self.source.next() {
+                Some(b'"') => break,
+                Some(b'{') if self.source.expect(b'{') => literal.push('{'),
+                Some(b'{') => {
+                    if !literal.is_empty() {
+                        parts.push(StringPart::Literal(mem::take(&mut literal)));
+                    }
+                    let code_position = self.source.current_position();
+                    match self.handle_embedded_code() {
+                        Some(code) => parts.push(StringPart::Code(code, code_position)),
+                        None => return TokenKind::Invalid(LexError::UnclosedInterpolation),
+                    }
+                }
+                _ => return TokenKind::Invalid(LexError::UnclosedString),
+            }
         }
 
-        Token {
-            kind: TokenKind::StringLiteral(string_contents.to_owned()),
-            position,
+        if parts.is_empty() {
+            TokenKind::StringLiteral(literal)
+        } else {
+            if !literal.is_empty() {
+                parts.push(StringPart::Literal(literal));
+            }
+            TokenKind::InterpolatedString(parts)
+        }
+    }
+
+    /// Consumes the source code of an expression embedded in a string
+    /// literal, up to and including the closing `}`. Braces inside nested
+    /// string literals are skipped over. Returns `None` if no closing brace
+    /// was found.
+    fn handle_embedded_code(&mut self) -> Option<String> {
+        let mut code = vec![];
+        let mut in_string = false;
+
+        loop {
+            match self.source.next() {
+                Some(b'}') if !in_string => return String::from_utf8(code).ok(),
+                Some(c) => {
+                    if c == b'"' {
+                        in_string = !in_string;
+                    }
+                    code.push(c);
+                }
+                None => return None,
+            }
         }
     }
 
     /// Consumes a one-byte or a two-byte operator, yielding an appropriate
     /// token.
-    fn handle_size_2_operator(&mut self, position: Position) -> Token {
+    fn handle_size_2_operator(&mut self) -> TokenKind {
         let c = self.source.next().unwrap();
-        let kind = match self.source.peek() {
+        match self.source.peek() {
             Some(b'=') => {
                 self.source.next();
                 match c {
@@ -219,6 +448,10 @@ impl<'s> Lexer<'s> {
                     _ => unreachable!(),
                 }
             }
+            Some(b'>') if c == b'=' => {
+                self.source.next();
+                TokenKind::FatArrow
+            }
             _ => match c {
                 b'=' => TokenKind::Equal,
                 b'!' => TokenKind::Bang,
@@ -226,8 +459,6 @@ impl<'s> Lexer<'s> {
                 b'<' => TokenKind::Less,
                 _ => unreachable!(),
             },
-        };
-
-        Token { kind, position }
+        }
     }
 }
@@ -6,7 +6,12 @@ macro_rules! hashmap {
     });
 }
 
+pub mod analysis;
+pub mod checker;
+pub mod formatter;
 pub mod lexer;
+pub mod lint;
 pub mod parser;
+pub mod repl;
 pub mod runtime;
 mod source_stream;
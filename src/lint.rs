@@ -0,0 +1,365 @@
+//! Static checks for likely mistakes in programs that parse fine.
+//!
+//! Each check is a `Rule`, which a `Config` turns on or off. Configurations
+//! are written in a subset of TOML, where every rule is on by default:
+//!
+//! ```toml
+//! [rules]
+//! unused-variable = false
+//! shadowing = true
+//! ```
+
+use crate::{
+    analysis::{past, Analysis, SymbolKind},
+    checker,
+    lexer::Position,
+    parser::*,
+    runtime::{builtin_arity, builtin_functions, RuntimeError},
+};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rule {
+    /// A variable that is assigned but never read.
+    UnusedVariable,
+    /// An assignment without `nonlocal` to a name defined in an outer scope,
+    /// which creates a new variable rather than updating that one.
+    Shadowing,
+    /// Statements after a `ret` in the same block.
+    UnreachableCode,
+    /// An `if` or `while` condition that can't be a boolean.
+    NonBooleanCondition,
+    /// A call to a function that is neither defined nor a builtin, which
+    /// fails when it runs.
+    UnknownFunction,
+    /// A call with more or fewer arguments than the function takes.
+    ArgumentCount,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Rule::UnusedVariable,
+        Rule::Shadowing,
+        Rule::UnreachableCode,
+        Rule::NonBooleanCondition,
+        Rule::UnknownFunction,
+        Rule::ArgumentCount,
+    ];
+
+    /// The name of the rule in configurations and reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::Shadowing => "shadowing",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::NonBooleanCondition => "non-boolean-condition",
+            Rule::UnknownFunction => "unknown-function",
+            Rule::ArgumentCount => "argument-count",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.iter().copied().find(|r| r.name() == name)
+    }
+}
+
+/// Which rules to check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    enabled: Vec<Rule>,
+}
+
+impl Default for Config {
+    /// Checks every rule.
+    fn default() -> Self {
+        Config {
+            enabled: Rule::ALL.to_vec(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads a configuration, failing on anything but comments, a `[rules]`
+    /// header, and rules set to `true` or `false`.
+    ///
+    /// ```
+    /// use kotoba::lint::{Config, Rule};
+    ///
+    /// let config = Config::parse("[rules]\nshadowing = false # too noisy").unwrap();
+    /// assert!(!config.is_enabled(Rule::Shadowing));
+    /// assert!(config.is_enabled(Rule::UnusedVariable));
+    /// assert_eq!(
+    ///     Config::parse("typo = true"),
+    ///     Err("line 1: unknown rule typo".to_owned())
+    /// );
+    /// ```
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() || line == "[rules]" {
+                continue;
+            }
+
+            let error = |message: String| format!("line {}: {}", i + 1, message);
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected `rule = true|false`, found {}", line)))?;
+            let (name, value) = (name.trim(), value.trim());
+
+            let rule =
+                Rule::from_name(name).ok_or_else(|| error(format!("unknown rule {}", name)))?;
+            match value {
+                "true" => config.set(rule, true),
+                "false" => config.set(rule, false),
+                _ => return Err(error(format!("expected true or false, found {}", value))),
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        self.enabled.contains(&rule)
+    }
+
+    pub fn set(&mut self, rule: Rule, enabled: bool) {
+        self.enabled.retain(|&r| r != rule);
+        if enabled {
+            self.enabled.push(rule);
+        }
+    }
+}
+
+/// A problem found by a rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    pub rule: Rule,
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "warning at {}: {} [{}]",
+            self.span.start,
+            self.message,
+            self.rule.name()
+        )
+    }
+}
+
+/// Checks `ast`, which must have been parsed from `source`, returning what
+/// the enabled rules found in source order.
+///
+/// ```
+/// use kotoba::{lint, parser::Parser};
+///
+/// let source = "x = 1, if x % 2: print(\"odd\");";
+/// let lints = lint::lint(source, &Parser::new(source).parse(), &lint::Config::default());
+/// assert_eq!(lints.len(), 1);
+/// assert_eq!(
+///     lints[0].to_string(),
+///     "warning at 1:11: condition is a number, not a boolean [non-boolean-condition]"
+/// );
+/// ```
+pub fn lint(source: &str, ast: &AstNode, config: &Config) -> Vec<Lint> {
+    let analysis = Analysis::new(source, ast);
+    let mut linter = Linter {
+        config,
+        analysis: &analysis,
+        lints: vec![],
+    };
+
+    linter.names();
+    linter.walk(ast);
+
+    let mut lints = linter.lints;
+    lints.sort_by_key(|l| (l.span.start.line, l.span.start.character));
+    lints
+}
+
+struct Linter<'a> {
+    config: &'a Config,
+    analysis: &'a Analysis,
+    lints: Vec<Lint>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, rule: Rule, span: Span, message: String) {
+        if self.config.is_enabled(rule) {
+            self.lints.push(Lint {
+                rule,
+                message,
+                span,
+            });
+        }
+    }
+
+    /// Checks the rules about names, which the analysis has resolved.
+    fn names(&mut self) {
+        let analysis = self.analysis;
+        for (index, symbol) in analysis.symbols.iter().enumerate() {
+            let variable = matches!(symbol.kind, SymbolKind::Variable | SymbolKind::Binding);
+            let used = analysis
+                .references
+                .iter()
+                .any(|r| r.symbol == Some(index) && !r.assignment);
+            if variable && !used && !symbol.name.starts_with('_') {
+                let message = format!("variable `{}` is never used", symbol.name);
+                self.report(Rule::UnusedVariable, symbol.span, message);
+            }
+
+            if let (SymbolKind::Variable, Some(outer)) = (symbol.kind, symbol.shadows) {
+                let outer = &analysis.symbols[outer];
+                let message = format!(
+                    "`{}` shadows the one defined at {}; use `nonlocal` to assign to it",
+                    symbol.name, outer.position
+                );
+                let span = name_span(symbol.position, &symbol.name);
+                self.report(Rule::Shadowing, span, message);
+            }
+        }
+
+        let builtins = builtin_functions();
+        for reference in &analysis.references {
+            let defined = reference.symbol.is_some() || builtins.contains(&reference.name);
+            if reference.function && !defined {
+                let span = name_span(reference.position, &reference.name);
+                let message = format!("call to unknown function `{}`", reference.name);
+                self.report(Rule::UnknownFunction, span, message);
+            }
+        }
+    }
+
+    /// Checks the rules about the structure of the program.
+    fn walk(&mut self, node: &AstNode) {
+        match &node.kind {
+            NodeKind::ProgramRoot(stmts) | NodeKind::Program(stmts) => {
+                if let Some(ret) = stmts
+                    .iter()
+                    .position(|s| matches!(s.kind, NodeKind::RetStmt(_)))
+                {
+                    if let (Some(first), Some(last)) = (stmts.get(ret + 1), stmts.last()) {
+                        let span = Span {
+                            start: first.span.start,
+                            end: last.span.end,
+                        };
+                        let message = "unreachable code after `ret`".to_owned();
+                        self.report(Rule::UnreachableCode, span, message);
+                    }
+                }
+                stmts.iter().for_each(|s| self.walk(s));
+            }
+            NodeKind::IfStmt {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                self.condition(condition);
+                self.walk(condition);
+                self.walk(then_body);
+                if let Some(else_body) = else_body {
+                    self.walk(else_body);
+                }
+            }
+            NodeKind::WhileStmt { condition, body } => {
+                self.condition(condition);
+                self.walk(condition);
+                self.walk(body);
+            }
+            NodeKind::Number(_)
+            | NodeKind::Boolean(_)
+            | NodeKind::StringLiteral(_)
+            | NodeKind::Identifier(_)
+            | NodeKind::Nil => {}
+            NodeKind::Interpolation(nodes) | NodeKind::List(nodes) => {
+                nodes.iter().for_each(|n| self.walk(n))
+            }
+            NodeKind::FnCall {
+                identifier,
+                args,
+                named,
+            } => {
+                self.call(node, identifier, args.len(), named.len());
+                args.iter().for_each(|a| self.walk(a));
+                named.iter().for_each(|(_, a)| self.walk(a));
+            }
+            NodeKind::Grouping(expr)
+            | NodeKind::RetStmt(expr)
+            | NodeKind::UnaryExpr { operand: expr, .. }
+            | NodeKind::Assignment { operand: expr, .. } => self.walk(expr),
+            NodeKind::FnStmt { params, body, .. } => {
+                params
+                    .iter()
+                    .filter_map(|p| p.default.as_ref())
+                    .for_each(|d| self.walk(d));
+                self.walk(body);
+            }
+            NodeKind::MethodCall { receiver, args, .. } => {
+                self.walk(receiver);
+                args.iter().for_each(|a| self.walk(a));
+            }
+            NodeKind::BinaryExpr { lhs, rhs, .. } => {
+                self.walk(lhs);
+                self.walk(rhs);
+            }
+            NodeKind::MatchStmt { scrutinee, arms } => {
+                self.walk(scrutinee);
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        self.walk(guard);
+                    }
+                    self.walk(&arm.body);
+                }
+            }
+        }
+    }
+
+    /// Checks the number of arguments of a call, if the function called is
+    /// known.
+    fn call(&mut self, node: &AstNode, identifier: &str, positional: usize, named: usize) {
+        let symbol = self
+            .analysis
+            .references
+            .iter()
+            .find(|r| r.function && r.position == node.span.start)
+            .and_then(|r| r.symbol);
+        let arity = match symbol {
+            Some(symbol) => self.analysis.symbols[symbol].arity,
+            None => builtin_arity(identifier),
+        };
+
+        if let Some(expected) = arity.filter(|a| !a.accepts_call(positional, named)) {
+            let error = RuntimeError::Arity {
+                function: identifier.to_owned(),
+                expected,
+                found: positional + named,
+            };
+            self.report(Rule::ArgumentCount, node.span, error.to_string());
+        }
+    }
+
+    fn condition(&mut self, condition: &AstNode) {
+        // type errors within the condition are for the checker to report
+        let type_name = match checker::infer(condition, &[]).0 {
+            TypeName::Num => "a number",
+            TypeName::Str => "a string",
+            TypeName::List => "a list",
+            TypeName::Nil => "nil",
+            TypeName::Bool | TypeName::Any => return,
+        };
+        let message = format!("condition is {}, not a boolean", type_name);
+        self.report(Rule::NonBooleanCondition, condition.span, message);
+    }
+}
+
+fn name_span(start: Position, name: &str) -> Span {
+    Span {
+        start,
+        end: past(start, name),
+    }
+}
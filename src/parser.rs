@@ -1,6 +1,11 @@
+mod dump;
+
 use crate::lexer::*;
+use std::fmt;
 
 type Result = std::result::Result<AstNode, Error>;
+/// The positional and the named arguments of a call.
+type Args = (Vec<AstNode>, Vec<(String, AstNode)>);
 
 #[derive(Debug)]
 enum Error {
@@ -12,24 +17,111 @@ enum Error {
     FnCallMissingCloseParen(Token),
     MissingIdentifier(Token),
     MissingParen(Token),
+    MissingFatArrow(Token),
+    InvalidPattern(Token),
+    UnclosedList(Token),
+    ParamAfterRest(Token),
+    RequiredParamAfterDefault(Token),
+    PositionalAfterNamedArg(Token),
+    InvalidType(Token),
+    /// A `nonlocal` that isn't followed by an assignment.
+    NonlocalWithoutAssignment(Token),
+    /// An `Invalid` token, which the lexer made of source it couldn't read.
+    Lexical(Token),
+    /// An error in the code of an interpolation. As the string closes that
+    /// code, appending to the source can't fix it.
+    Interpolation(SyntaxError),
+}
+
+/// A syntax error, as reported by `Parser::try_parse`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub position: Position,
+    /// Whether the source ended where more of it was expected, as within an
+    /// unclosed block or grouping, so that appending to it may fix it.
+    pub incomplete: bool,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "syntax error at {}: {}", self.position, self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Warning {
+    NonExhaustiveMatch { token: Token, missing: Vec<Pattern> },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::NonExhaustiveMatch { token, missing } => {
+                let missing = missing
+                    .iter()
+                    .map(|p| format!("`{}`", p))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(
+                    f,
+                    "warning: non-exhaustive match at {}: {} not covered",
+                    token.position, missing
+                )
+            }
+        }
+    }
+}
+
+/// A node of the syntax tree, along with the part of the source it was
+/// parsed from.
+#[derive(PartialEq, Clone)]
+pub struct AstNode {
+    pub kind: NodeKind,
+    pub span: Span,
+}
+
+/// Spans are left out, to keep dumps of whole trees readable.
+impl fmt::Debug for AstNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+/// The region of the source from the first byte of a node's first token up
+/// to, but not including, the position after its last token.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub enum AstNode {
+pub enum NodeKind {
     Program(Vec<AstNode>),
     ProgramRoot(Vec<AstNode>),
 
     Number(f64),
     Boolean(bool),
     StringLiteral(String),
+    Interpolation(Vec<AstNode>),
     Identifier(String),
     Nil,
 
     Grouping(Box<AstNode>),
+    List(Vec<AstNode>),
 
     FnCall {
         identifier: String,
         args: Vec<AstNode>,
+        /// The arguments given by parameter name, as in `f(b: 3)`, which
+        /// come after the positional ones.
+        named: Vec<(String, AstNode)>,
+    },
+    MethodCall {
+        receiver: Box<AstNode>,
+        method: String,
+        args: Vec<AstNode>,
     },
     RetStmt(Box<AstNode>),
 
@@ -47,6 +139,8 @@ pub enum AstNode {
         identifier: String,
         operand: Box<AstNode>,
         nonlocal: bool,
+        /// The type declared for the variable, as in `x: num = 1`.
+        annotation: Option<TypeName>,
     },
     IfStmt {
         condition: Box<AstNode>,
@@ -59,16 +153,119 @@ pub enum AstNode {
     },
     FnStmt {
         identifier: String,
-        params: Vec<String>,
+        params: Vec<Param>,
+        /// The type declared for the result, as in `fn f() -> num:`.
+        returns: Option<TypeName>,
         body: Box<AstNode>,
     },
+    MatchStmt {
+        scrutinee: Box<AstNode>,
+        arms: Vec<MatchArm>,
+    },
+}
+
+/// A parameter of a function: `a`, `b: num = 2` or `*rest`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Param {
+    pub name: String,
+    /// The type declared for the parameter, or for each argument a rest
+    /// parameter collects.
+    pub annotation: Option<TypeName>,
+    /// The value the parameter takes when no argument is given for it,
+    /// evaluated on each call after the parameters before it are bound.
+    pub default: Option<AstNode>,
+    /// Whether the parameter collects the remaining positional arguments
+    /// into a list. Only the last parameter may.
+    pub rest: bool,
+}
+
+/// A type written in an annotation. Annotations are only checked statically,
+/// by the `checker`, and code without them stays dynamically typed.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TypeName {
+    Num,
+    Str,
+    Bool,
+    List,
+    Nil,
+    /// Any type at all, which is also what unannotated code has.
+    Any,
+}
+
+impl TypeName {
+    pub fn from_name(name: &str) -> Option<TypeName> {
+        Some(match name {
+            "num" => TypeName::Num,
+            "str" => TypeName::Str,
+            "bool" => TypeName::Bool,
+            "list" => TypeName::List,
+            "nil" => TypeName::Nil,
+            "any" => TypeName::Any,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for TypeName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TypeName::Num => "num",
+            TypeName::Str => "str",
+            TypeName::Bool => "bool",
+            TypeName::List => "list",
+            TypeName::Nil => "nil",
+            TypeName::Any => "any",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Box<AstNode>>,
+    pub body: Box<AstNode>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Binding(String),
+    Number(f64),
+    Boolean(bool),
+    StringLiteral(String),
+    Nil,
+    List(Vec<Pattern>),
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Binding(id) => write!(f, "{}", id),
+            Pattern::Number(n) => write!(f, "{}", n),
+            Pattern::Boolean(b) => write!(f, "{}", b),
+            Pattern::StringLiteral(s) => write!(f, "\"{}\"", s),
+            Pattern::Nil => write!(f, "nil"),
+            Pattern::List(items) => write!(
+                f,
+                "[{}]",
+                items
+                    .iter()
+                    .map(Pattern::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Op {
     Bang,
     Star,
+    StarStar,
     Slash,
+    TildeSlash,
     Plus,
     Minus,
     Percent,
@@ -82,12 +279,38 @@ pub enum Op {
     Or,
 }
 
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            Op::Bang => "!",
+            Op::Star => "*",
+            Op::StarStar => "**",
+            Op::Slash => "/",
+            Op::TildeSlash => "~/",
+            Op::Plus => "+",
+            Op::Minus => "-",
+            Op::Percent => "%",
+            Op::Greater => ">",
+            Op::GreaterEqual => ">=",
+            Op::Less => "<",
+            Op::LessEqual => "<=",
+            Op::EqualEqual => "==",
+            Op::BangEqual => "!=",
+            Op::And => "and",
+            Op::Or => "or",
+        };
+        f.write_str(op)
+    }
+}
+
 impl From<&TokenKind> for Op {
     fn from(kind: &TokenKind) -> Self {
         match kind {
             TokenKind::Bang => Op::Bang,
             TokenKind::Star => Op::Star,
+            TokenKind::StarStar => Op::StarStar,
             TokenKind::Slash => Op::Slash,
+            TokenKind::TildeSlash => Op::TildeSlash,
             TokenKind::Plus => Op::Plus,
             TokenKind::Minus => Op::Minus,
             TokenKind::Percent => Op::Percent,
@@ -106,28 +329,175 @@ impl From<&TokenKind> for Op {
 
 pub struct Parser<'source> {
     lexer: Lexer<'source>,
+    warnings: Vec<Warning>,
 }
 
 impl<'source> Parser<'source> {
     pub fn new(source: &'source str) -> Self {
         Parser {
             lexer: Lexer::new(source),
+            warnings: vec![],
         }
     }
 
+    /// Returns the warnings collected so far. Warnings do not prevent the
+    /// program from being evaluated.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Returns the comments skipped over so far, in source order.
+    pub fn comments(&self) -> &[Comment] {
+        self.lexer.comments()
+    }
+
     pub fn parse(&mut self) -> AstNode {
         // TODO: consume token stream
-        match self.parse_program() {
-            Ok(AstNode::Program(p)) => AstNode::ProgramRoot(p),
+        match self.parse_root() {
+            Ok(root) => root,
             Err(err) => {
                 println!("syntax error: {:#?}", err);
-                AstNode::Nil
+                let start = self.lexer.last_end();
+                self.node(start, NodeKind::Nil)
             }
+        }
+    }
+
+    /// Parses the whole source, returning the first syntax error instead of
+    /// printing it. Unlike `parse`, also fails if anything is left over after
+    /// the program, such as a stray `;`.
+    pub fn try_parse(&mut self) -> std::result::Result<AstNode, SyntaxError> {
+        let root = self.parse_root().map_err(|err| self.syntax_error(err))?;
+        match self.lexer.next() {
+            Some(t) => Err(self.syntax_error(Error::UnexpectedToken(t))),
+            None => Ok(root),
+        }
+    }
+
+    fn parse_root(&mut self) -> Result {
+        let program = self.parse_program()?;
+        match program.kind {
+            NodeKind::Program(p) => Ok(AstNode {
+                kind: NodeKind::ProgramRoot(p),
+                span: program.span,
+            }),
             _ => unreachable!(),
         }
     }
 
+    fn syntax_error(&mut self, err: Error) -> SyntaxError {
+        // the parser stops at an invalid token, if not before it, so that's
+        // the error to report once the lexer has made one
+        let err = match self.lexer.error() {
+            Some(t) => Error::Lexical(t.clone()),
+            None => err,
+        };
+        let expecting = matches!(
+            err,
+            Error::UnclosedGrouping(_)
+                | Error::MissingColon(_)
+                | Error::MissingSemicolon(_)
+                | Error::FnCallMissingCloseParen(_)
+                | Error::MissingIdentifier(_)
+                | Error::MissingParen(_)
+                | Error::MissingFatArrow(_)
+                | Error::UnclosedList(_)
+        );
+        let incomplete = expecting && self.lexer.peek().is_none();
+
+        let (message, token) = match err {
+            Error::Interpolation(error) => return error,
+            Error::UnexpectedEof => {
+                return SyntaxError {
+                    message: "unexpected end of input".to_owned(),
+                    position: self.lexer.last_end(),
+                    incomplete: true,
+                }
+            }
+            Error::UnclosedGrouping(t) => ("unclosed `(`".to_owned(), t),
+            Error::UnexpectedToken(t) => (format!("unexpected `{}`", t.kind), t),
+            Error::MissingColon(t) => (format!("missing `:` after `{}`", t.kind), t),
+            Error::MissingSemicolon(t) => (format!("missing `;` closing `{}`", t.kind), t),
+            Error::FnCallMissingCloseParen(t) => {
+                (format!("missing `)` closing the call to `{}`", t.kind), t)
+            }
+            Error::MissingIdentifier(t) => (format!("missing identifier after `{}`", t.kind), t),
+            Error::MissingParen(t) => (format!("missing parenthesis after `{}`", t.kind), t),
+            Error::MissingFatArrow(t) => (format!("expected `=>`, found `{}`", t.kind), t),
+            Error::InvalidPattern(t) => (format!("invalid pattern `{}`", t.kind), t),
+            Error::UnclosedList(t) => ("unclosed `[`".to_owned(), t),
+            Error::ParamAfterRest(t) => (
+                format!("parameter `{}` after the rest parameter", t.kind),
+                t,
+            ),
+            Error::RequiredParamAfterDefault(t) => (
+                format!(
+                    "parameter `{}` needs a default, as one before it has",
+                    t.kind
+                ),
+                t,
+            ),
+            Error::InvalidType(t) => (format!("expected a type, found `{}`", t.kind), t),
+            Error::NonlocalWithoutAssignment(t) => {
+                ("expected an assignment after `nonlocal`".to_owned(), t)
+            }
+            Error::Lexical(t) => {
+                return SyntaxError {
+                    message: t.kind.to_string(),
+                    position: t.position,
+                    incomplete: matches!(&t.kind, TokenKind::Invalid(e) if e.is_incomplete()),
+                }
+            }
+            Error::PositionalAfterNamedArg(t) => (
+                format!(
+                    "positional argument after a named one in the call to `{}`",
+                    t.kind
+                ),
+                t,
+            ),
+        };
+
+        SyntaxError {
+            message,
+            position: token.position,
+            incomplete,
+        }
+    }
+
+    /// Returns the position of the next token, or where the source ends.
+    fn next_position(&mut self) -> Position {
+        match self.lexer.peek() {
+            Some(t) => t.position,
+            None => self.lexer.last_end(),
+        }
+    }
+
+    /// Wraps `kind` into a node spanning from `start` to the end of the last
+    /// token consumed.
+    fn node(&self, start: Position, kind: NodeKind) -> AstNode {
+        AstNode {
+            kind,
+            span: Span {
+                start,
+                end: self.lexer.last_end(),
+            },
+        }
+    }
+
+    fn binary(&self, operator: Op, lhs: AstNode, rhs: AstNode) -> AstNode {
+        let start = lhs.span.start;
+        self.node(
+            start,
+            NodeKind::BinaryExpr {
+                operator,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+        )
+    }
+
     fn parse_program(&mut self) -> Result {
+        let start = self.next_position();
         let mut stmts = vec![];
 
         while let Some(t) = self.lexer.peek() {
@@ -146,37 +516,117 @@ impl<'source> Parser<'source> {
                     self.lexer.next();
                     stmts.push(self.parse_fn(t)?)
                 }
-                TokenKind::Ret => {
+                TokenKind::Match => {
                     self.lexer.next();
-                    stmts.push(AstNode::RetStmt(Box::new(self.parse_expression()?)))
+                    stmts.push(self.parse_match(t)?)
                 }
-                TokenKind::Nonlocal => {
-                    self.lexer.next();
-                    let ret = match self.parse_expression()? {
-                        AstNode::Assignment {
-                            identifier,
-                            operand,
-                            ..
-                        } => AstNode::Assignment {
-                            identifier,
-                            operand,
-                            nonlocal: true,
-                        },
-                        _ => panic!("not an assigment?"),
+                _ => {
+                    let stmt = match t.kind {
+                        TokenKind::Ret => {
+                            self.lexer.next();
+                            let expr = self.parse_expression()?;
+                            self.node(t.position, NodeKind::RetStmt(Box::new(expr)))
+                        }
+                        TokenKind::Nonlocal => {
+                            self.lexer.next();
+                            match self.parse_expression()? {
+                                AstNode {
+                                    kind:
+                                        NodeKind::Assignment {
+                                            identifier,
+                                            operand,
+                                            ..
+                                        },
+                                    ..
+                                } => self.node(
+                                    t.position,
+                                    NodeKind::Assignment {
+                                        identifier,
+                                        operand,
+                                        nonlocal: true,
+                                        annotation: None,
+                                    },
+                                ),
+                                _ => return Err(Error::NonlocalWithoutAssignment(t)),
+                            }
+                        }
+                        _ => {
+                            let expr = self.parse_expression()?;
+                            self.parse_annotated(expr)?
+                        }
                     };
 
-                    stmts.push(ret)
-                }
-                _ => {
-                    stmts.push(self.parse_expression()?);
-                    if self.lexer.expect(&TokenKind::Comma).is_none() {
+                    let ends_with_block = Parser::ends_with_block(&stmt);
+                    stmts.push(stmt);
+
+                    if self.lexer.expect(&TokenKind::Comma).is_none() && !ends_with_block {
                         break;
                     }
                 }
             }
         }
 
-        Ok(AstNode::Program(stmts))
+        Ok(self.node(start, NodeKind::Program(stmts)))
+    }
+
+    /// Parses the rest of an annotated assignment such as `x: num = 1`, if
+    /// the statement `expr` is the identifier it starts with followed by a
+    /// `:`. Otherwise, returns `expr` as it is.
+    fn parse_annotated(&mut self, expr: AstNode) -> Result {
+        let identifier = match &expr.kind {
+            NodeKind::Identifier(identifier) => identifier.clone(),
+            _ => return Ok(expr),
+        };
+        if self.lexer.expect(&TokenKind::Colon).is_none() {
+            return Ok(expr);
+        }
+
+        let annotation = self.parse_type()?;
+        match self.lexer.next() {
+            Some(Token {
+                kind: TokenKind::Equal,
+                ..
+            }) => {}
+            Some(t) => return Err(Error::UnexpectedToken(t)),
+            None => return Err(Error::UnexpectedEof),
+        }
+
+        let operand = self.parse_expression()?;
+        Ok(self.node(
+            expr.span.start,
+            NodeKind::Assignment {
+                identifier,
+                operand: Box::new(operand),
+                nonlocal: false,
+                annotation: Some(annotation),
+            },
+        ))
+    }
+
+    /// Parses the type of an annotation, after its `:` or `->`.
+    fn parse_type(&mut self) -> std::result::Result<TypeName, Error> {
+        let t = self.lexer.next().ok_or(Error::UnexpectedEof)?;
+        let name = match &t.kind {
+            TokenKind::Identifier(name) => TypeName::from_name(name),
+            TokenKind::Nil => Some(TypeName::Nil),
+            _ => None,
+        };
+        name.ok_or(Error::InvalidType(t))
+    }
+
+    /// Returns `true` if the last token of `expr` is the `;` closing an
+    /// `if` or a `match`, in which case (just like after a block statement)
+    /// the `,` separating it from the next statement may be omitted.
+    pub(crate) fn ends_with_block(expr: &AstNode) -> bool {
+        match &expr.kind {
+            NodeKind::IfStmt { .. } | NodeKind::MatchStmt { .. } => true,
+            NodeKind::Assignment { operand, .. } | NodeKind::UnaryExpr { operand, .. } => {
+                Parser::ends_with_block(operand)
+            }
+            NodeKind::BinaryExpr { rhs, .. } => Parser::ends_with_block(rhs),
+            NodeKind::RetStmt(expr) => Parser::ends_with_block(expr),
+            _ => false,
+        }
     }
 
     fn parse_if(&mut self, t: Token) -> Result {
@@ -198,11 +648,14 @@ impl<'source> Parser<'source> {
             return Err(Error::MissingSemicolon(t));
         }
 
-        Ok(AstNode::IfStmt {
-            condition: Box::new(condition),
-            then_body: Box::new(then_body),
-            else_body: else_body.map(Box::new),
-        })
+        Ok(self.node(
+            t.position,
+            NodeKind::IfStmt {
+                condition: Box::new(condition),
+                then_body: Box::new(then_body),
+                else_body: else_body.map(Box::new),
+            },
+        ))
     }
 
     fn parse_while(&mut self, t: Token) -> Result {
@@ -218,10 +671,13 @@ impl<'source> Parser<'source> {
             return Err(Error::MissingSemicolon(t));
         }
 
-        Ok(AstNode::WhileStmt {
-            condition: Box::new(condition),
-            body: Box::new(body),
-        })
+        Ok(self.node(
+            t.position,
+            NodeKind::WhileStmt {
+                condition: Box::new(condition),
+                body: Box::new(body),
+            },
+        ))
     }
 
     fn parse_fn(&mut self, t: Token) -> Result {
@@ -230,22 +686,28 @@ impl<'source> Parser<'source> {
             .expect_identifier()
             .ok_or_else(|| Error::MissingIdentifier(t.clone()))?;
 
-        if self.lexer.expect(&TokenKind::OpenParen).is_none() {
-            return Err(Error::MissingParen(t));
-        }
+        let open = match self.lexer.expect(&TokenKind::OpenParen) {
+            Some(open) => open,
+            None => return Err(Error::MissingParen(t)),
+        };
 
         let mut params = vec![];
-        if let Some(p) = self.lexer.expect_identifier() {
-            params.push(p);
+        if self.lexer.expect(&TokenKind::CloseParen).is_none() {
+            params.push(self.parse_param(open, &params)?);
+            while let Some(comma) = self.lexer.expect(&TokenKind::Comma) {
+                params.push(self.parse_param(comma, &params)?);
+            }
 
-            while self.lexer.expect(&TokenKind::Comma).is_some() {
-                params.push(self.lexer.expect_identifier().unwrap());
+            if self.lexer.expect(&TokenKind::CloseParen).is_none() {
+                return Err(Error::MissingParen(t));
             }
         }
 
-        if self.lexer.expect(&TokenKind::CloseParen).is_none() {
-            return Err(Error::MissingParen(t));
-        }
+        let returns = if self.lexer.expect(&TokenKind::Arrow).is_some() {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
 
         if self.lexer.expect(&TokenKind::Colon).is_none() {
             return Err(Error::MissingColon(t));
@@ -257,13 +719,176 @@ impl<'source> Parser<'source> {
             return Err(Error::MissingSemicolon(t));
         }
 
-        Ok(AstNode::FnStmt {
-            identifier,
-            params,
-            body: Box::new(body),
+        Ok(self.node(
+            t.position,
+            NodeKind::FnStmt {
+                identifier,
+                params,
+                returns,
+                body: Box::new(body),
+            },
+        ))
+    }
+
+    /// Parses a parameter following the token `t`, given the parameters
+    /// `before` it.
+    fn parse_param(&mut self, t: Token, before: &[Param]) -> std::result::Result<Param, Error> {
+        let star = self.lexer.expect(&TokenKind::Star);
+        let rest = star.is_some();
+        let name = match self.lexer.peek() {
+            Some(Token {
+                kind: TokenKind::Identifier(name),
+                ..
+            }) => name,
+            _ => return Err(Error::MissingIdentifier(star.unwrap_or(t))),
+        };
+        let token = self.lexer.next().unwrap();
+
+        if before.last().is_some_and(|p| p.rest) {
+            return Err(Error::ParamAfterRest(token));
+        }
+
+        let annotation = if self.lexer.expect(&TokenKind::Colon).is_some() {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
+        let default = if !rest && self.lexer.expect(&TokenKind::Equal).is_some() {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+        if default.is_none() && !rest && before.iter().any(|p| p.default.is_some()) {
+            return Err(Error::RequiredParamAfterDefault(token));
+        }
+
+        Ok(Param {
+            name,
+            annotation,
+            default,
+            rest,
+        })
+    }
+
+    fn parse_match(&mut self, t: Token) -> Result {
+        let scrutinee = self.parse_expression()?;
+
+        if self.lexer.expect(&TokenKind::Colon).is_none() {
+            return Err(Error::MissingColon(t));
+        }
+
+        let mut arms = vec![self.parse_match_arm()?];
+        while self.lexer.expect(&TokenKind::Comma).is_some() {
+            arms.push(self.parse_match_arm()?);
+        }
+
+        if self.lexer.expect(&TokenKind::Semicolon).is_none() {
+            return Err(Error::MissingSemicolon(t));
+        }
+
+        let missing = Parser::missing_patterns(&arms);
+        if !missing.is_empty() {
+            self.warnings.push(Warning::NonExhaustiveMatch {
+                token: t.clone(),
+                missing,
+            });
+        }
+
+        Ok(self.node(
+            t.position,
+            NodeKind::MatchStmt {
+                scrutinee: Box::new(scrutinee),
+                arms,
+            },
+        ))
+    }
+
+    fn parse_match_arm(&mut self) -> std::result::Result<MatchArm, Error> {
+        let pattern = self.parse_pattern()?;
+
+        let guard = if self.lexer.expect(&TokenKind::If).is_some() {
+            Some(Box::new(self.parse_expression()?))
+        } else {
+            None
+        };
+
+        if let Some(t) = self.lexer.peek() {
+            if self.lexer.expect(&TokenKind::FatArrow).is_none() {
+                return Err(Error::MissingFatArrow(t));
+            }
+        } else {
+            return Err(Error::UnexpectedEof);
+        }
+
+        Ok(MatchArm {
+            pattern,
+            guard,
+            body: Box::new(self.parse_expression()?),
         })
     }
 
+    fn parse_pattern(&mut self) -> std::result::Result<Pattern, Error> {
+        let t = self.lexer.next().ok_or(Error::UnexpectedEof)?;
+
+        match t.kind {
+            TokenKind::Identifier(ref id) if id == "_" => Ok(Pattern::Wildcard),
+            TokenKind::Identifier(id) => Ok(Pattern::Binding(id)),
+            TokenKind::Number(n) => Ok(Pattern::Number(n)),
+            TokenKind::Minus => match self.lexer.next() {
+                Some(Token {
+                    kind: TokenKind::Number(n),
+                    ..
+                }) => Ok(Pattern::Number(-n)),
+                _ => Err(Error::InvalidPattern(t)),
+            },
+            TokenKind::Boolean(b) => Ok(Pattern::Boolean(b)),
+            TokenKind::StringLiteral(s) => Ok(Pattern::StringLiteral(s)),
+            TokenKind::Nil => Ok(Pattern::Nil),
+            TokenKind::OpenBracket => {
+                let mut items = vec![];
+                if self.lexer.expect(&TokenKind::CloseBracket).is_none() {
+                    items.push(self.parse_pattern()?);
+                    while self.lexer.expect(&TokenKind::Comma).is_some() {
+                        items.push(self.parse_pattern()?);
+                    }
+
+                    if self.lexer.expect(&TokenKind::CloseBracket).is_none() {
+                        return Err(Error::UnclosedList(t));
+                    }
+                }
+
+                Ok(Pattern::List(items))
+            }
+            _ => Err(Error::InvalidPattern(t)),
+        }
+    }
+
+    /// Returns the patterns that a match over booleans (and possibly `nil`)
+    /// fails to cover. Matches over any other values are assumed to be
+    /// intentionally partial, so nothing is reported for them.
+    fn missing_patterns(arms: &[MatchArm]) -> Vec<Pattern> {
+        let patterns: Vec<_> = arms.iter().map(|a| &a.pattern).collect();
+        let over_booleans = patterns
+            .iter()
+            .all(|p| matches!(p, Pattern::Boolean(_) | Pattern::Nil));
+
+        if !over_booleans {
+            return vec![];
+        }
+
+        let mut required = vec![Pattern::Boolean(true), Pattern::Boolean(false)];
+        if patterns.contains(&&Pattern::Nil) {
+            required.push(Pattern::Nil);
+        }
+
+        // guarded arms may be skipped, so they don't count towards coverage
+        required
+            .into_iter()
+            .filter(|r| !arms.iter().any(|a| a.guard.is_none() && a.pattern == *r))
+            .collect()
+    }
+
     fn parse_expression(&mut self) -> Result {
         self.parse_disjunction()
     }
@@ -272,11 +897,8 @@ impl<'source> Parser<'source> {
         let mut acc = self.parse_conjunction()?;
 
         while self.lexer.expect(&TokenKind::Or).is_some() {
-            acc = AstNode::BinaryExpr {
-                operator: Op::Or,
-                lhs: Box::new(acc),
-                rhs: Box::new(self.parse_conjunction()?),
-            }
+            let rhs = self.parse_conjunction()?;
+            acc = self.binary(Op::Or, acc, rhs);
         }
 
         Ok(acc)
@@ -286,11 +908,8 @@ impl<'source> Parser<'source> {
         let mut acc = self.parse_equality()?;
 
         while self.lexer.expect(&TokenKind::And).is_some() {
-            acc = AstNode::BinaryExpr {
-                operator: Op::And,
-                lhs: Box::new(acc),
-                rhs: Box::new(self.parse_equality()?),
-            }
+            let rhs = self.parse_equality()?;
+            acc = self.binary(Op::And, acc, rhs);
         }
 
         Ok(acc)
@@ -303,11 +922,8 @@ impl<'source> Parser<'source> {
             .lexer
             .expect_any(&[TokenKind::EqualEqual, TokenKind::BangEqual])
         {
-            return Ok(AstNode::BinaryExpr {
-                operator: (&t.kind).into(),
-                lhs: Box::new(lhs),
-                rhs: Box::new(self.parse_comparison()?),
-            });
+            let rhs = self.parse_comparison()?;
+            return Ok(self.binary((&t.kind).into(), lhs, rhs));
         }
 
         Ok(lhs)
@@ -322,11 +938,8 @@ impl<'source> Parser<'source> {
             TokenKind::Less,
             TokenKind::LessEqual,
         ]) {
-            return Ok(AstNode::BinaryExpr {
-                operator: (&t.kind).into(),
-                lhs: Box::new(lhs),
-                rhs: Box::new(self.parse_modulo()?),
-            });
+            let rhs = self.parse_modulo()?;
+            return Ok(self.binary((&t.kind).into(), lhs, rhs));
         }
 
         Ok(lhs)
@@ -336,11 +949,8 @@ impl<'source> Parser<'source> {
         let mut acc = self.parse_addition()?;
 
         while let Some(t) = self.lexer.expect(&TokenKind::Percent) {
-            acc = AstNode::BinaryExpr {
-                operator: (&t.kind).into(),
-                lhs: Box::new(acc),
-                rhs: Box::new(self.parse_addition()?),
-            }
+            let rhs = self.parse_addition()?;
+            acc = self.binary((&t.kind).into(), acc, rhs);
         }
 
         Ok(acc)
@@ -350,11 +960,8 @@ impl<'source> Parser<'source> {
         let mut acc = self.parse_multiplication()?;
 
         while let Some(t) = self.lexer.expect_any(&[TokenKind::Plus, TokenKind::Minus]) {
-            acc = AstNode::BinaryExpr {
-                operator: (&t.kind).into(),
-                lhs: Box::new(acc),
-                rhs: Box::new(self.parse_multiplication()?),
-            }
+            let rhs = self.parse_multiplication()?;
+            acc = self.binary((&t.kind).into(), acc, rhs);
         }
 
         Ok(acc)
@@ -363,12 +970,12 @@ impl<'source> Parser<'source> {
     fn parse_multiplication(&mut self) -> Result {
         let mut acc = self.parse_unary()?;
 
-        while let Some(t) = self.lexer.expect_any(&[TokenKind::Star, TokenKind::Slash]) {
-            acc = AstNode::BinaryExpr {
-                operator: (&t.kind).into(),
-                lhs: Box::new(acc),
-                rhs: Box::new(self.parse_unary()?),
-            }
+        while let Some(t) =
+            self.lexer
+                .expect_any(&[TokenKind::Star, TokenKind::Slash, TokenKind::TildeSlash])
+        {
+            let rhs = self.parse_unary()?;
+            acc = self.binary((&t.kind).into(), acc, rhs);
         }
 
         Ok(acc)
@@ -376,75 +983,246 @@ impl<'source> Parser<'source> {
 
     fn parse_unary(&mut self) -> Result {
         if let Some(t) = self.lexer.expect_any(&[TokenKind::Bang, TokenKind::Minus]) {
-            return Ok(AstNode::UnaryExpr {
-                operator: (&t.kind).into(),
-                operand: Box::new(self.parse_unary()?),
-            });
+            let operand = self.parse_unary()?;
+            return Ok(self.node(
+                t.position,
+                NodeKind::UnaryExpr {
+                    operator: (&t.kind).into(),
+                    operand: Box::new(operand),
+                },
+            ));
         }
 
-        self.parse_primary()
+        self.parse_power()
+    }
+
+    fn parse_power(&mut self) -> Result {
+        let lhs = self.parse_postfix()?;
+
+        // right-associative, and binds tighter than a unary minus on its left
+        if let Some(t) = self.lexer.expect(&TokenKind::StarStar) {
+            let rhs = self.parse_unary()?;
+            return Ok(self.binary((&t.kind).into(), lhs, rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    /// Parses a chain of method calls, like `a.b().c(1)`.
+    fn parse_postfix(&mut self) -> Result {
+        let mut acc = self.parse_primary()?;
+
+        while let Some(t) = self.lexer.expect(&TokenKind::Dot) {
+            let method = match self.lexer.next() {
+                Some(Token {
+                    kind: TokenKind::Identifier(method),
+                    ..
+                }) => method,
+                Some(t) => return Err(Error::MissingIdentifier(t)),
+                None => return Err(Error::UnexpectedEof),
+            };
+
+            if self.lexer.expect(&TokenKind::OpenParen).is_none() {
+                return Err(Error::MissingParen(t));
+            }
+
+            let start = acc.span.start;
+            let (args, _) = self.parse_args(t, false)?;
+            acc = self.node(
+                start,
+                NodeKind::MethodCall {
+                    receiver: Box::new(acc),
+                    method,
+                    args,
+                },
+            );
+        }
+
+        Ok(acc)
     }
 
     fn parse_primary(&mut self) -> Result {
         if let Some(t) = self.lexer.next() {
-            match t.kind.clone() {
-                TokenKind::Number(n) => Ok(AstNode::Number(n)),
-                TokenKind::Boolean(b) => Ok(AstNode::Boolean(b)),
-                TokenKind::StringLiteral(s) => Ok(AstNode::StringLiteral(s)),
-                TokenKind::Identifier(identifier) => self.parse_identifier(identifier, t),
-                TokenKind::Nil => Ok(AstNode::Nil),
-                TokenKind::OpenParen => self.parse_grouping(t),
-                _ => Err(Error::UnexpectedToken(t)),
-            }
+            let kind = match t.kind.clone() {
+                TokenKind::Number(n) => NodeKind::Number(n),
+                TokenKind::Boolean(b) => NodeKind::Boolean(b),
+                TokenKind::StringLiteral(s) => NodeKind::StringLiteral(s),
+                TokenKind::InterpolatedString(parts) => self.parse_interpolation(parts, &t)?,
+                TokenKind::Identifier(identifier) => return self.parse_identifier(identifier, t),
+                TokenKind::Nil => NodeKind::Nil,
+                TokenKind::OpenParen => self.parse_grouping(&t)?,
+                TokenKind::OpenBracket => self.parse_list(&t)?,
+                TokenKind::If => return self.parse_if(t),
+                TokenKind::Match => return self.parse_match(t),
+                _ => return Err(Error::UnexpectedToken(t)),
+            };
+
+            Ok(self.node(t.position, kind))
         } else {
             Err(Error::UnexpectedEof)
         }
     }
 
     fn parse_identifier(&mut self, identifier: String, t: Token) -> Result {
-        if self.lexer.expect(&TokenKind::OpenParen).is_some() {
+        let kind = if self.lexer.expect(&TokenKind::OpenParen).is_some() {
             // fn call
-            let mut args = vec![];
-
-            if self.lexer.expect(&TokenKind::CloseParen).is_some() {
-                Ok(AstNode::FnCall { identifier, args })
-            } else if self.lexer.peek().is_some() {
-                if let Ok(arg) = self.parse_expression() {
-                    args.push(arg);
-
-                    while self.lexer.expect(&TokenKind::Comma).is_some() {
-                        args.push(self.parse_expression()?);
-                    }
-                }
-
-                if self.lexer.expect(&TokenKind::CloseParen).is_none() {
-                    Err(Error::FnCallMissingCloseParen(t))
-                } else {
-                    Ok(AstNode::FnCall { identifier, args })
-                }
-            } else {
-                Err(Error::FnCallMissingCloseParen(t))
+            let (args, named) = self.parse_args(t.clone(), true)?;
+            NodeKind::FnCall {
+                identifier,
+                args,
+                named,
             }
         } else if self.lexer.expect(&TokenKind::Equal).is_some() {
             // assignment
-            Ok(AstNode::Assignment {
+            NodeKind::Assignment {
                 identifier,
                 operand: Box::new(self.parse_expression()?),
                 nonlocal: false,
-            })
+                annotation: None,
+            }
         } else {
             // variable access
-            Ok(AstNode::Identifier(identifier))
+            NodeKind::Identifier(identifier)
+        };
+
+        Ok(self.node(t.position, kind))
+    }
+
+    /// Parses the arguments of a call, after its opening paren: the
+    /// positional ones, then the named ones if `named` calls allow them.
+    fn parse_args(&mut self, t: Token, named: bool) -> std::result::Result<Args, Error> {
+        let mut args = (vec![], vec![]);
+
+        if self.lexer.expect(&TokenKind::CloseParen).is_some() {
+            Ok(args)
+        } else if self.lexer.peek().is_some() {
+            if let Ok(arg) = self.parse_expression() {
+                self.push_arg(&mut args, arg, named, &t)?;
+
+                while self.lexer.expect(&TokenKind::Comma).is_some() {
+                    let arg = self.parse_expression()?;
+                    self.push_arg(&mut args, arg, named, &t)?;
+                }
+            }
+
+            if self.lexer.expect(&TokenKind::CloseParen).is_none() {
+                Err(Error::FnCallMissingCloseParen(t))
+            } else {
+                Ok(args)
+            }
+        } else {
+            Err(Error::FnCallMissingCloseParen(t))
+        }
+    }
+
+    /// Adds `arg` to the arguments of the call `t`, as a named argument if it
+    /// is an identifier followed by `:`.
+    fn push_arg(
+        &mut self,
+        (args, named_args): &mut Args,
+        arg: AstNode,
+        named: bool,
+        t: &Token,
+    ) -> std::result::Result<(), Error> {
+        match arg.kind {
+            NodeKind::Identifier(name)
+                if named && self.lexer.expect(&TokenKind::Colon).is_some() =>
+            {
+                named_args.push((name, self.parse_expression()?));
+            }
+            _ if !named_args.is_empty() => return Err(Error::PositionalAfterNamedArg(t.clone())),
+            _ => args.push(arg),
+        }
+        Ok(())
+    }
+
+    /// Parses each embedded expression of an interpolated string literal with
+    /// a nested parser, yielding the literal text and the expressions in
+    /// order. The literal text is given the span of the whole string.
+    fn parse_interpolation(
+        &mut self,
+        parts: Vec<StringPart>,
+        t: &Token,
+    ) -> std::result::Result<NodeKind, Error> {
+        let mut nodes = vec![];
+        let span = Span {
+            start: t.position,
+            end: t.end,
+        };
+
+        for part in parts {
+            match part {
+                StringPart::Literal(s) => nodes.push(AstNode {
+                    kind: NodeKind::StringLiteral(s),
+                    span,
+                }),
+                StringPart::Code(code, position) => {
+                    let mut parser = Parser {
+                        lexer: Lexer::with_position(&code, position),
+                        warnings: vec![],
+                    };
+
+                    let expr =
+                        parser
+                            .parse_expression()
+                            .and_then(|expr| match parser.lexer.next() {
+                                Some(t) => Err(Error::UnexpectedToken(t)),
+                                None => Ok(expr),
+                            });
+                    let expr = match expr {
+                        Ok(expr) => expr,
+                        // the code stops at the closing brace
+                        Err(Error::UnexpectedEof) if parser.lexer.error().is_none() => {
+                            return Err(Error::Interpolation(SyntaxError {
+                                message: "expected an expression before `}`".to_owned(),
+                                position: parser.lexer.last_end(),
+                                incomplete: false,
+                            }))
+                        }
+                        Err(err) => {
+                            let error = parser.syntax_error(err);
+                            return Err(Error::Interpolation(SyntaxError {
+                                incomplete: false,
+                                ..error
+                            }));
+                        }
+                    };
+
+                    self.warnings.append(&mut parser.warnings);
+                    nodes.push(expr);
+                }
+            }
+        }
+
+        Ok(NodeKind::Interpolation(nodes))
+    }
+
+    fn parse_list(&mut self, t: &Token) -> std::result::Result<NodeKind, Error> {
+        let mut items = vec![];
+
+        if self.lexer.expect(&TokenKind::CloseBracket).is_some() {
+            return Ok(NodeKind::List(items));
+        }
+
+        items.push(self.parse_expression()?);
+        while self.lexer.expect(&TokenKind::Comma).is_some() {
+            items.push(self.parse_expression()?);
+        }
+
+        if self.lexer.expect(&TokenKind::CloseBracket).is_some() {
+            Ok(NodeKind::List(items))
+        } else {
+            Err(Error::UnclosedList(t.clone()))
         }
     }
 
-    fn parse_grouping(&mut self, t: Token) -> Result {
+    fn parse_grouping(&mut self, t: &Token) -> std::result::Result<NodeKind, Error> {
         let expr = self.parse_expression()?;
 
         if self.lexer.expect(&TokenKind::CloseParen).is_some() {
-            Ok(AstNode::Grouping(Box::new(expr)))
+            Ok(NodeKind::Grouping(Box::new(expr)))
         } else {
-            Err(Error::UnclosedGrouping(t))
+            Err(Error::UnclosedGrouping(t.clone()))
         }
     }
 }
@@ -0,0 +1,421 @@
+//! Textual dumps of syntax trees, for inspecting what the parser produced.
+
+use super::{AstNode, MatchArm, NodeKind, Param, Span, TypeName};
+use crate::lexer::Position;
+use std::fmt::Write;
+
+/// Lines of S-expressions longer than this are broken up.
+const WIDTH: usize = 80;
+
+enum Sexpr {
+    Atom(String),
+    List(Vec<Sexpr>),
+}
+
+fn atom(s: impl ToString) -> Sexpr {
+    Sexpr::Atom(s.to_string())
+}
+
+/// A name along with its type, if annotated, as in `a:num`.
+fn annotated(name: &str, annotation: Option<TypeName>) -> Sexpr {
+    match annotation {
+        Some(annotation) => atom(format!("{}:{}", name, annotation)),
+        None => atom(name),
+    }
+}
+
+impl Sexpr {
+    fn flat(&self) -> String {
+        match self {
+            Sexpr::Atom(s) => s.clone(),
+            Sexpr::List(items) => format!(
+                "({})",
+                items.iter().map(Sexpr::flat).collect::<Vec<_>>().join(" ")
+            ),
+        }
+    }
+
+    /// Prints the expression on one line if it fits, or else its head and
+    /// first atoms on one line and every other item on a line of its own.
+    fn pretty(&self, indent: usize, out: &mut String) {
+        let flat = self.flat();
+        let items = match self {
+            Sexpr::List(items) if indent + flat.len() > WIDTH => items,
+            _ => return out.push_str(&flat),
+        };
+
+        out.push('(');
+        let head = items
+            .iter()
+            .take_while(|i| matches!(i, Sexpr::Atom(_)))
+            .count()
+            .max(1);
+        let head_items = items[..head].iter().map(Sexpr::flat).collect::<Vec<_>>();
+        out.push_str(&head_items.join(" "));
+
+        for item in &items[head..] {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent + 2));
+            item.pretty(indent + 2, out);
+        }
+        out.push(')');
+    }
+}
+
+impl AstNode {
+    /// Dumps the tree as indented S-expressions, such as
+    /// `(program (= x (+ 1 2)))`. Spans are left out.
+    pub fn to_sexpr(&self) -> String {
+        let mut out = String::new();
+        self.sexpr().pretty(0, &mut out);
+        out
+    }
+
+    fn sexpr(&self) -> Sexpr {
+        let list = |head: &str, rest: Vec<Sexpr>| {
+            let mut items = vec![atom(head)];
+            items.extend(rest);
+            Sexpr::List(items)
+        };
+        let all = |nodes: &[AstNode]| nodes.iter().map(AstNode::sexpr).collect::<Vec<_>>();
+
+        match &self.kind {
+            NodeKind::ProgramRoot(stmts) => list("program", all(stmts)),
+            NodeKind::Program(stmts) => list("block", all(stmts)),
+            NodeKind::Number(n) => atom(n),
+            NodeKind::Boolean(b) => atom(b),
+            NodeKind::StringLiteral(s) => atom(format!("{:?}", s)),
+            NodeKind::Interpolation(parts) => list("interpolate", all(parts)),
+            NodeKind::Identifier(id) => atom(id),
+            NodeKind::Nil => atom("nil"),
+            NodeKind::Grouping(expr) => list("group", vec![expr.sexpr()]),
+            NodeKind::List(items) => list("list", all(items)),
+            NodeKind::FnCall {
+                identifier,
+                args,
+                named,
+            } => {
+                let mut items = vec![atom(identifier)];
+                items.extend(all(args));
+                items.extend(
+                    named
+                        .iter()
+                        .map(|(name, arg)| list("named", vec![atom(name), arg.sexpr()])),
+                );
+                list("call", items)
+            }
+            NodeKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                let mut items = vec![atom(method), receiver.sexpr()];
+                items.extend(all(args));
+                list("method", items)
+            }
+            NodeKind::RetStmt(expr) => list("ret", vec![expr.sexpr()]),
+            NodeKind::UnaryExpr { operator, operand } => {
+                list(&operator.to_string(), vec![operand.sexpr()])
+            }
+            NodeKind::BinaryExpr { operator, lhs, rhs } => {
+                list(&operator.to_string(), vec![lhs.sexpr(), rhs.sexpr()])
+            }
+            NodeKind::Assignment {
+                identifier,
+                operand,
+                nonlocal,
+                annotation,
+            } => list(
+                if *nonlocal { "nonlocal=" } else { "=" },
+                vec![annotated(identifier, *annotation), operand.sexpr()],
+            ),
+            NodeKind::IfStmt {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                let mut items = vec![condition.sexpr(), then_body.sexpr()];
+                items.extend(else_body.iter().map(|e| e.sexpr()));
+                list("if", items)
+            }
+            NodeKind::WhileStmt { condition, body } => {
+                list("while", vec![condition.sexpr(), body.sexpr()])
+            }
+            NodeKind::FnStmt {
+                identifier,
+                params,
+                returns,
+                body,
+            } => {
+                let mut items = vec![
+                    atom(identifier),
+                    Sexpr::List(params.iter().map(Param::sexpr).collect()),
+                ];
+                if let Some(returns) = returns {
+                    items.push(Sexpr::List(vec![atom("->"), atom(returns)]));
+                }
+                items.push(body.sexpr());
+                list("fn", items)
+            }
+            NodeKind::MatchStmt { scrutinee, arms } => {
+                let mut items = vec![scrutinee.sexpr()];
+                items.extend(arms.iter().map(MatchArm::sexpr));
+                list("match", items)
+            }
+        }
+    }
+
+    /// Serializes the tree as JSON. Every node is an object with a `kind`,
+    /// named after its `NodeKind` variant, a `span`, and the variant's fields.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+
+    fn write_json(&self, out: &mut String) {
+        let obj = JsonObject::new(out);
+        let obj = match &self.kind {
+            NodeKind::ProgramRoot(stmts) => obj.kind("ProgramRoot").nodes("stmts", stmts),
+            NodeKind::Program(stmts) => obj.kind("Program").nodes("stmts", stmts),
+            NodeKind::Number(n) if n.is_finite() => obj.kind("Number").raw("value", &n.to_string()),
+            NodeKind::Number(_) => obj.kind("Number").raw("value", "null"),
+            NodeKind::Boolean(b) => obj.kind("Boolean").raw("value", &b.to_string()),
+            NodeKind::StringLiteral(s) => obj.kind("StringLiteral").string("value", s),
+            NodeKind::Interpolation(parts) => obj.kind("Interpolation").nodes("parts", parts),
+            NodeKind::Identifier(id) => obj.kind("Identifier").string("name", id),
+            NodeKind::Nil => obj.kind("Nil"),
+            NodeKind::Grouping(expr) => obj.kind("Grouping").node("expr", expr),
+            NodeKind::List(items) => obj.kind("List").nodes("items", items),
+            NodeKind::FnCall {
+                identifier,
+                args,
+                named,
+            } => {
+                let named = named.iter().map(|(name, arg)| {
+                    let mut json = String::new();
+                    JsonObject::new(&mut json)
+                        .string("name", name)
+                        .node("value", arg)
+                        .end();
+                    json
+                });
+                obj.kind("FnCall")
+                    .string("identifier", identifier)
+                    .nodes("args", args)
+                    .raw("named", &json_array(named))
+            }
+            NodeKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => obj
+                .kind("MethodCall")
+                .node("receiver", receiver)
+                .string("method", method)
+                .nodes("args", args),
+            NodeKind::RetStmt(expr) => obj.kind("RetStmt").node("expr", expr),
+            NodeKind::UnaryExpr { operator, operand } => obj
+                .kind("UnaryExpr")
+                .string("operator", &operator.to_string())
+                .node("operand", operand),
+            NodeKind::BinaryExpr { operator, lhs, rhs } => obj
+                .kind("BinaryExpr")
+                .string("operator", &operator.to_string())
+                .node("lhs", lhs)
+                .node("rhs", rhs),
+            NodeKind::Assignment {
+                identifier,
+                operand,
+                nonlocal,
+                annotation,
+            } => obj
+                .kind("Assignment")
+                .string("identifier", identifier)
+                .node("operand", operand)
+                .raw("nonlocal", &nonlocal.to_string())
+                .type_name("annotation", *annotation),
+            NodeKind::IfStmt {
+                condition,
+                then_body,
+                else_body,
+            } => {
+                let obj = obj
+                    .kind("IfStmt")
+                    .node("condition", condition)
+                    .node("then_body", then_body);
+                match else_body {
+                    Some(else_body) => obj.node("else_body", else_body),
+                    None => obj.raw("else_body", "null"),
+                }
+            }
+            NodeKind::WhileStmt { condition, body } => obj
+                .kind("WhileStmt")
+                .node("condition", condition)
+                .node("body", body),
+            NodeKind::FnStmt {
+                identifier,
+                params,
+                returns,
+                body,
+            } => obj
+                .kind("FnStmt")
+                .string("identifier", identifier)
+                .raw("params", &json_array(params.iter().map(Param::json)))
+                .type_name("returns", *returns)
+                .node("body", body),
+            NodeKind::MatchStmt { scrutinee, arms } => {
+                let mut json = String::from("[");
+                for (i, arm) in arms.iter().enumerate() {
+                    if i > 0 {
+                        json.push(',');
+                    }
+                    arm.write_json(&mut json);
+                }
+                json.push(']');
+
+                obj.kind("MatchStmt")
+                    .node("scrutinee", scrutinee)
+                    .raw("arms", &json)
+            }
+        };
+        obj.span(self.span).end();
+    }
+}
+
+impl MatchArm {
+    fn sexpr(&self) -> Sexpr {
+        let mut items = vec![atom("arm"), atom(&self.pattern)];
+        if let Some(guard) = &self.guard {
+            items.push(Sexpr::List(vec![atom("if"), guard.sexpr()]));
+        }
+        items.push(self.body.sexpr());
+        Sexpr::List(items)
+    }
+
+    fn write_json(&self, out: &mut String) {
+        let obj = JsonObject::new(out).string("pattern", &self.pattern.to_string());
+        let obj = match &self.guard {
+            Some(guard) => obj.node("guard", guard),
+            None => obj.raw("guard", "null"),
+        };
+        obj.node("body", &self.body).end();
+    }
+}
+
+impl Param {
+    /// `b`, `(= b 2)` for a default, or `*b` for a rest parameter, with
+    /// the type after a `:` if annotated, as in `b:num`.
+    fn sexpr(&self) -> Sexpr {
+        let name = annotated(&self.name, self.annotation);
+        match &self.default {
+            Some(default) => Sexpr::List(vec![atom("="), name, default.sexpr()]),
+            None if self.rest => atom(format!("*{}", name.flat())),
+            None => name,
+        }
+    }
+
+    fn json(&self) -> String {
+        let mut out = String::new();
+        let obj = JsonObject::new(&mut out)
+            .string("name", &self.name)
+            .type_name("annotation", self.annotation);
+        let obj = match &self.default {
+            Some(default) => obj.node("default", default),
+            None => obj.raw("default", "null"),
+        };
+        obj.raw("rest", &self.rest.to_string()).end();
+        out
+    }
+}
+
+/// Writes the members of a JSON object one after another.
+struct JsonObject<'a> {
+    out: &'a mut String,
+    empty: bool,
+}
+
+impl<'a> JsonObject<'a> {
+    fn new(out: &'a mut String) -> Self {
+        out.push('{');
+        JsonObject { out, empty: true }
+    }
+
+    fn raw(mut self, key: &str, json: &str) -> Self {
+        if !self.empty {
+            self.out.push(',');
+        }
+        self.empty = false;
+        write!(self.out, "{}:{}", json_string(key), json).unwrap();
+        self
+    }
+
+    fn kind(self, kind: &str) -> Self {
+        self.string("kind", kind)
+    }
+
+    fn string(self, key: &str, value: &str) -> Self {
+        self.raw(key, &json_string(value))
+    }
+
+    fn type_name(self, key: &str, type_name: Option<TypeName>) -> Self {
+        match type_name {
+            Some(type_name) => self.string(key, &type_name.to_string()),
+            None => self.raw(key, "null"),
+        }
+    }
+
+    fn span(self, span: Span) -> Self {
+        let position =
+            |p: Position| format!("{{\"line\":{},\"character\":{}}}", p.line, p.character);
+        let json = format!(
+            "{{\"start\":{},\"end\":{}}}",
+            position(span.start),
+            position(span.end)
+        );
+        self.raw("span", &json)
+    }
+
+    fn node(self, key: &str, node: &AstNode) -> Self {
+        let mut json = String::new();
+        node.write_json(&mut json);
+        self.raw(key, &json)
+    }
+
+    fn nodes(self, key: &str, nodes: &[AstNode]) -> Self {
+        let mut json = String::from("[");
+        for (i, node) in nodes.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            node.write_json(&mut json);
+        }
+        json.push(']');
+        self.raw(key, &json)
+    }
+
+    fn end(self) {
+        self.out.push('}');
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_array(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(","))
+}
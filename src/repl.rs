@@ -0,0 +1,284 @@
+//! The interactive prompt: reads entries with line editing and history,
+//! evaluates them one after another in the same environment, and shows
+//! their values or what went wrong, without ending the session.
+//!
+//! An entry spans as many lines as it takes to complete it, so that blocks
+//! such as `while x < 3:` may be continued on the following lines.
+//!
+//! Entries starting with `:` are commands to the prompt itself, which show
+//! what the interpreter makes of some code, or manage the environment; see
+//! `:help` for the list.
+//!
+//! On a terminal, entries are colored as they are typed, and the tab key
+//! completes keywords, builtins and the names defined so far.
+
+mod editor;
+
+pub use self::editor::highlight;
+
+use self::editor::EditorHelper;
+use crate::{
+    checker, formatter,
+    lexer::Lexer,
+    parser::{Parser, SyntaxError, TypeName},
+    runtime::{builtin_constants, Env, Type},
+};
+use rustyline::{error::ReadlineError, history::DefaultHistory};
+use std::{cell::RefCell, env, fs, path::PathBuf, rc::Rc, time::Instant};
+
+const PROMPT: &str = "::<> ";
+const CONTINUATION: &str = "...  ";
+
+const HELP: &str = "\
+:help          show this help
+:load FILE     run the program in FILE, keeping what it defines
+:env           list the variables and functions defined so far
+:ast CODE      show how CODE is parsed
+:tokens CODE   show the tokens CODE is made of
+:type CODE     show the type CODE evaluates to, without running it
+:time CODE     run CODE and show how long it took
+:reset         forget every variable and function defined so far";
+
+const COMMANDS: [&str; 8] = [
+    "ast", "env", "help", "load", "reset", "time", "tokens", "type",
+];
+
+/// The commands whose argument is code.
+const CODE_COMMANDS: [&str; 4] = ["ast", "time", "tokens", "type"];
+
+type Editor = rustyline::Editor<EditorHelper, DefaultHistory>;
+
+/// The state kept from one entry to the next.
+pub struct Session {
+    env: Rc<RefCell<Env>>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session { env: Env::new() }
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session::default()
+    }
+
+    /// Evaluates an entry, returning what to show for it: its value, or the
+    /// errors that stopped it, after any warnings. Commands return what they
+    /// show, which may be nothing.
+    ///
+    /// ```
+    /// use kotoba::repl::Session;
+    ///
+    /// let mut session = Session::new();
+    /// assert_eq!(session.eval("x = 2"), "=> nil");
+    /// assert_eq!(session.eval("x * 3"), "=> 6");
+    /// assert_eq!(
+    ///     session.eval("y = \"a\", x - y"),
+    ///     "runtime error: `-` can't be applied to number and string"
+    /// );
+    /// assert_eq!(session.eval(":type x / 4"), "num");
+    /// assert_eq!(session.eval(":env"), "x = 2\ny = \"a\"");
+    /// ```
+    pub fn eval(&mut self, entry: &str) -> String {
+        match entry.trim_start().strip_prefix(':') {
+            Some(command) => self.command(command.trim_end()),
+            None => self.eval_code(entry),
+        }
+    }
+
+    fn eval_code(&mut self, source: &str) -> String {
+        let mut parser = Parser::new(source);
+        let ast = match parser.try_parse() {
+            Ok(ast) => ast,
+            Err(err) => return err.to_string(),
+        };
+        let mut lines: Vec<_> = parser.warnings().iter().map(|w| w.to_string()).collect();
+
+        let errors = checker::check_in(&ast, &self.env.borrow());
+        if errors.is_empty() {
+            lines.push(match Env::eval(self.env.clone(), &ast) {
+                Ok(value) => format!("=> {}", value),
+                Err(err) => format!("runtime error: {}", err),
+            });
+        } else {
+            // type errors stop the entry before it runs
+            lines.extend(errors.iter().map(|e| e.to_string()));
+        }
+        lines.join("\n")
+    }
+
+    /// Runs the command `:{command}`, where `command` holds its name and
+    /// argument.
+    fn command(&mut self, command: &str) -> String {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim_start()),
+            None => (command, ""),
+        };
+
+        match (name, argument) {
+            ("help", _) => HELP.to_owned(),
+            ("env", _) => self.definitions(),
+            ("reset", _) => {
+                self.env = Env::new();
+                String::new()
+            }
+            ("load", "") => "usage: :load FILE".to_owned(),
+            ("ast" | "tokens" | "type" | "time", "") => format!("usage: :{} CODE", name),
+            ("load", path) => match fs::read_to_string(path) {
+                Ok(source) => self.eval_code(&source),
+                Err(err) => format!("error: cannot read {}: {}", path, err),
+            },
+            ("ast", code) => match Parser::new(code).try_parse() {
+                Ok(ast) => ast.to_sexpr(),
+                Err(err) => err.to_string(),
+            },
+            ("tokens", code) => Lexer::new(code)
+                .map(|t| format!("{}-{} {:?}", t.position, t.end, t.kind))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ("type", code) => self.type_of(code),
+            ("time", code) => {
+                let start = Instant::now();
+                let output = self.eval_code(code);
+                format!("{}\ntook {:?}", output, start.elapsed())
+            }
+            _ => format!("unknown command `:{}`; see :help", name),
+        }
+    }
+
+    /// Lists the variables and the functions defined in the session, other
+    /// than the builtin ones.
+    fn definitions(&self) -> String {
+        let env = self.env.borrow();
+        let constants = builtin_constants();
+        let variables = env
+            .variables()
+            .into_iter()
+            .filter(|(name, _)| !constants.iter().any(|c| c == name))
+            .map(|(name, value)| format!("{} = {}", name, value));
+        let functions = env
+            .functions()
+            .into_iter()
+            .map(|(name, params)| formatter::signature(name, params));
+        variables.chain(functions).collect::<Vec<_>>().join("\n")
+    }
+
+    /// Infers the type of `code` from the values of the variables and the
+    /// functions defined so far, without running it.
+    fn type_of(&self, code: &str) -> String {
+        let ast = match Parser::new(code).try_parse() {
+            Ok(ast) => ast,
+            Err(err) => return err.to_string(),
+        };
+        let env = self.env.borrow();
+        let variables: Vec<_> = env
+            .variables()
+            .into_iter()
+            .map(|(name, value)| (name, type_name(value)))
+            .collect();
+
+        match checker::infer_in(&ast, &variables, &env) {
+            (result, errors) if errors.is_empty() => result.to_string(),
+            (_, errors) => {
+                let errors: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+                errors.join("\n")
+            }
+        }
+    }
+}
+
+/// Returns the static type of `value`.
+fn type_name(value: &Type) -> TypeName {
+    match value {
+        Type::Number(_) => TypeName::Num,
+        Type::Boolean(_) => TypeName::Bool,
+        Type::String(_) => TypeName::Str,
+        Type::List(_) => TypeName::List,
+        Type::Object(_) => TypeName::Any,
+        Type::Nil => TypeName::Nil,
+    }
+}
+
+/// Returns whether `entry` stops short of a whole program, such as within
+/// an unclosed block, grouping or string, so that the lines after it may
+/// complete it.
+///
+/// ```
+/// use kotoba::repl::is_incomplete;
+///
+/// assert!(is_incomplete("while x < 3:"));
+/// assert!(is_incomplete("print(\"a"));
+/// assert!(!is_incomplete("while x < 3: x = x + 1;"));
+/// assert!(!is_incomplete("x = )"));
+/// ```
+pub fn is_incomplete(entry: &str) -> bool {
+    match Parser::new(entry).try_parse() {
+        Ok(_) => false,
+        Err(SyntaxError { incomplete, .. }) => incomplete,
+    }
+}
+
+/// Where the history is kept between sessions: `.kotoba_history` in the
+/// home directory.
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".kotoba_history"))
+}
+
+/// Runs the prompt on the terminal until the end of input (Ctrl-D). Ctrl-C
+/// discards the entry being typed.
+pub fn run() -> rustyline::Result<()> {
+    let mut editor = Editor::new()?;
+    editor.set_helper(Some(EditorHelper {
+        session: Session::new(),
+    }));
+    let history = history_path();
+    if let Some(path) = &history {
+        // there is none yet on the first run
+        editor.load_history(path).ok();
+    }
+
+    loop {
+        let entry = match read_entry(&mut editor) {
+            Ok(entry) => entry,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        if entry.trim().is_empty() {
+            continue;
+        }
+
+        editor.add_history_entry(entry.as_str())?;
+        if let Some(path) = &history {
+            // saved after each entry, in case the session ends with `exit()`;
+            // failing to is no reason to end it
+            editor.save_history(path).ok();
+        }
+
+        let helper = editor.helper_mut().expect("the helper is set");
+        let output = helper.session.eval(&entry);
+        if !output.is_empty() {
+            println!("{}", output);
+        }
+    }
+}
+
+/// Reads lines until they make a complete entry, or the input ends.
+/// Commands take a single line.
+fn read_entry(editor: &mut Editor) -> rustyline::Result<String> {
+    let mut entry = editor.readline(PROMPT)?;
+    while !entry.trim_start().starts_with(':') && is_incomplete(&entry) {
+        match editor.readline(CONTINUATION) {
+            Ok(line) => {
+                entry.push('\n');
+                entry.push_str(&line);
+            }
+            // evaluating what was read reports what's missing
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(entry)
+}
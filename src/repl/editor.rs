@@ -0,0 +1,214 @@
+//! Syntax highlighting and tab completion of the entries being typed.
+
+use super::{Session, CODE_COMMANDS, COMMANDS};
+use crate::{
+    lexer::{Lexer, Position, TokenKind},
+    runtime::builtin_functions,
+};
+use rustyline::{
+    completion::Completer,
+    highlight::{CmdKind, Highlighter},
+    hint::Hinter,
+    validate::Validator,
+    Context, Helper,
+};
+use std::borrow::Cow;
+
+const KEYWORDS: [&str; 12] = [
+    "and", "else", "false", "fn", "if", "match", "nil", "nonlocal", "or", "ret", "true", "while",
+];
+
+const RESET: &str = "\x1b[0m";
+const COMMAND: &str = "\x1b[1m";
+const KEYWORD: &str = "\x1b[35m";
+const LITERAL: &str = "\x1b[33m";
+const STRING: &str = "\x1b[32m";
+const OPERATOR: &str = "\x1b[36m";
+const COMMENT: &str = "\x1b[90m";
+
+/// What the line editor consults while an entry is typed. It holds the
+/// session, to complete the names defined in it.
+pub(super) struct EditorHelper {
+    pub(super) session: Session,
+}
+
+impl Completer for EditorHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.session.complete(line, pos))
+    }
+}
+
+impl Highlighter for EditorHelper {
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight(line))
+    }
+
+    /// The colors don't depend on where the cursor is.
+    fn highlight_char(&self, _: &str, _: usize, kind: CmdKind) -> bool {
+        kind != CmdKind::MoveCursor
+    }
+}
+
+impl Hinter for EditorHelper {
+    type Hint = String;
+}
+
+impl Validator for EditorHelper {}
+
+impl Helper for EditorHelper {}
+
+impl Session {
+    /// Returns where the word that ends at `pos` in `line` starts, and the
+    /// names it may be completed to, in alphabetical order: keywords,
+    /// builtins and the names defined in the session, or the names of
+    /// commands after a `:`.
+    ///
+    /// ```
+    /// use kotoba::repl::Session;
+    ///
+    /// let mut session = Session::new();
+    /// session.eval("total = 0");
+    /// let (start, names) = session.complete("x = t", 5);
+    /// assert_eq!(start, 4);
+    /// assert_eq!(names, ["tan", "time", "total", "trim", "true", "type_of"]);
+    /// assert_eq!(session.complete(":t", 2).1, ["time", "tokens", "type"]);
+    /// assert_eq!(session.complete("\"é\" + tr", 9), (7, vec!["trim".into(), "true".into()]));
+    /// assert_eq!(session.complete("x = \"é", 7), (7, vec![]));
+    /// ```
+    pub fn complete(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let before = &line[..pos];
+        let start = before
+            .char_indices()
+            .rev()
+            .find(|&(_, c)| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let word = &before[start..];
+
+        let command = before[..start]
+            .trim_start()
+            .strip_prefix(':')
+            .map(|c| c.split(' ').next().unwrap_or_default());
+        let names = match command {
+            Some("") => COMMANDS.iter().map(|&c| c.to_owned()).collect(),
+            Some(command) if !CODE_COMMANDS.contains(&command) => return (pos, vec![]),
+            // everything would match
+            _ if word.is_empty() => return (pos, vec![]),
+            _ => self.names(),
+        };
+
+        let mut names: Vec<_> = names.into_iter().filter(|n| n.starts_with(word)).collect();
+        names.sort();
+        names.dedup();
+        (start, names)
+    }
+
+    /// Returns the names that code may refer to.
+    fn names(&self) -> Vec<String> {
+        let env = self.env.borrow();
+        let variables = env.variables().into_iter().map(|(name, _)| name);
+        let functions = env.functions().into_iter().map(|(name, _)| name);
+
+        KEYWORDS
+            .iter()
+            .copied()
+            .chain(variables)
+            .chain(functions)
+            .map(str::to_owned)
+            .chain(builtin_functions())
+            .collect()
+    }
+}
+
+/// Colors the keywords, literals, operators and comments of `line` for the
+/// terminal, and the name of a command.
+///
+/// ```
+/// use kotoba::repl::highlight;
+///
+/// assert_eq!(
+///     highlight("x = \"a\""),
+///     "x \u{1b}[36m=\u{1b}[0m \u{1b}[32m\"a\"\u{1b}[0m"
+/// );
+/// ```
+pub fn highlight(line: &str) -> String {
+    let indent = line.len() - line.trim_start().len();
+    if !line[indent..].starts_with(':') {
+        return highlight_code(line);
+    }
+
+    let end = line[indent..]
+        .find(char::is_whitespace)
+        .map_or(line.len(), |i| indent + i);
+    let (command, argument) = line.split_at(end);
+    let code = CODE_COMMANDS.contains(&&command[indent + 1..]);
+    format!(
+        "{}{}{}{}{}",
+        &command[..indent],
+        COMMAND,
+        &command[indent..],
+        RESET,
+        if code {
+            highlight_code(argument)
+        } else {
+            argument.to_owned()
+        }
+    )
+}
+
+fn highlight_code(code: &str) -> String {
+    let mut lexer = Lexer::new(code);
+    let tokens: Vec<_> = (&mut lexer).collect();
+
+    let mut spans: Vec<_> = tokens
+        .iter()
+        .filter_map(|t| {
+            let style = style(&t.kind)?;
+            Some((offset(code, t.position), offset(code, t.end), style))
+        })
+        .chain(lexer.comments().iter().map(|c| {
+            let start = offset(code, c.position);
+            (start, start + c.text.len(), COMMENT)
+        }))
+        .collect();
+    spans.sort_by_key(|&(start, ..)| start);
+
+    let mut highlighted = String::new();
+    let mut done = 0;
+    for (start, end, style) in spans {
+        highlighted.push_str(&code[done..start]);
+        highlighted.push_str(&format!("{}{}{}", style, &code[start..end], RESET));
+        done = end;
+    }
+    highlighted.push_str(&code[done..]);
+    highlighted
+}
+
+fn style(kind: &TokenKind) -> Option<&'static str> {
+    use TokenKind::*;
+    match kind {
+        Number(_) | Boolean(_) | Nil => Some(LITERAL),
+        StringLiteral(_) | InterpolatedString(_) => Some(STRING),
+        Invalid(err) if err.is_incomplete() => Some(STRING),
+        If | Else | While | Fn | Ret | Match | Nonlocal | And | Or => Some(KEYWORD),
+        Identifier(_) | OpenParen | CloseParen | OpenBracket | CloseBracket | Dot | Colon
+        | Comma | Semicolon | Invalid(_) => None,
+        _ => Some(OPERATOR),
+    }
+}
+
+/// Returns the index in `code` of the byte at `position`.
+fn offset(code: &str, position: Position) -> usize {
+    let line_start: usize = code
+        .split_inclusive('\n')
+        .take(position.line - 1)
+        .map(str::len)
+        .sum();
+    line_start + position.character - 1
+}
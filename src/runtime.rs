@@ -1,24 +1,80 @@
+mod arity;
+mod error;
+mod host;
+mod native;
+mod object;
 mod prelude;
+#[cfg(feature = "serde")]
+mod serialize;
+mod signature;
 
+#[cfg(feature = "serde")]
+pub use self::serialize::{from_value, to_value};
+pub use self::{
+    arity::Arity,
+    error::{Limit, RuntimeError},
+    host::{CancelHandle, Capabilities, EnvBuilder, Limits},
+    native::{FromValue, IntoNativeResult, IntoValue, Native, NativeFn},
+    object::{Class, ClassHandle, Object},
+    prelude::{builtin_arity, builtin_constants, builtin_functions, builtin_signature},
+    signature::Signature,
+};
+
+use self::host::Host;
 use crate::parser::*;
 use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
 
 type Result = std::result::Result<Type, Internal>;
 
+/// How much stack must be left for evaluating a node, and how big the stack
+/// segments allocated when there isn't are, in bytes.
+const STACK_RED_ZONE: usize = 64 * 1024;
+const STACK_SEGMENT: usize = 1024 * 1024;
+type NamedArgs = Vec<(String, Type)>;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Number(f64),
     Boolean(bool),
     String(String),
+    List(Vec<Type>),
+    Object(Object),
     Nil,
 }
 
+impl Type {
+    /// Returns the name of the value's type, as reported by `type_of`.
+    /// Objects report the name of their class.
+    pub fn type_name(&self) -> &str {
+        match self {
+            Type::Number(_) => "number",
+            Type::Boolean(_) => "boolean",
+            Type::String(_) => "string",
+            Type::List(_) => "list",
+            Type::Object(object) => object.class_name(),
+            Type::Nil => "nil",
+        }
+    }
+}
+
+/// Strings are displayed quoted, unless the alternate flag (`{:#}`) is
+/// given, which is how they end up in the program's output.
 impl fmt::Display for Type {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let out = match self {
             Type::Number(n) => n.to_string(),
             Type::Boolean(b) => b.to_string(),
+            Type::String(s) if f.alternate() => s.clone(),
             Type::String(s) => format!("\"{}\"", s.clone()),
+            Type::List(items) => format!(
+                "[{}]",
+                items
+                    .iter()
+                    .map(Type::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Type::Object(object) => object.to_string(),
             Type::Nil => "nil".to_string(),
         };
         write!(f, "{}", out)
@@ -28,20 +84,87 @@ impl fmt::Display for Type {
 #[derive(Debug)]
 enum Internal {
     Return(Type),
+    Error(RuntimeError),
 }
 
-#[derive(Debug)]
-#[allow(dead_code)]
+impl From<RuntimeError> for Internal {
+    fn from(err: RuntimeError) -> Self {
+        Internal::Error(err)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub(crate) enum Callable {
-    Builtin(fn(Vec<Type>) -> Type),
-    UserDefined, // TODO: impl
+    /// A builtin, with its arity and the types of its parameters and result.
+    Builtin(
+        fn(&Host, Vec<Type>) -> std::result::Result<Type, RuntimeError>,
+        Arity,
+        &'static [TypeName],
+        TypeName,
+    ),
+    Native(Native),
+    UserDefined {
+        params: Rc<[Param]>,
+        returns: Option<TypeName>,
+        body: Rc<AstNode>,
+    },
 }
 
 impl Callable {
-    fn call(&self, args: Vec<Type>) -> Type {
+    fn arity(&self) -> Arity {
         match self {
-            Callable::Builtin(f) => f(args),
-            Callable::UserDefined => unimplemented!(),
+            Callable::Builtin(_, arity, ..) => *arity,
+            Callable::Native(f) => f.arity(),
+            Callable::UserDefined { params, .. } => Arity::of_params(params),
+        }
+    }
+
+    fn signature(&self) -> Signature {
+        match self {
+            Callable::Builtin(_, arity, params, returns) => {
+                Signature::unnamed(*arity, params, *returns)
+            }
+            Callable::Native(f) => f.signature().clone(),
+            Callable::UserDefined {
+                params, returns, ..
+            } => Signature::of_params(params, *returns),
+        }
+    }
+
+    /// Calls the function `name`, with `env` being the environment it was
+    /// defined in. The number of arguments must have been checked against its
+    /// arity. Only user-defined functions take named arguments.
+    fn call(&self, name: &str, env: Rc<RefCell<Env>>, args: Vec<Type>, named: NamedArgs) -> Result {
+        if let (Callable::Builtin(..) | Callable::Native(_), Some((param, _))) =
+            (self, named.first())
+        {
+            return Err(Internal::Error(RuntimeError::UnknownParameter {
+                function: name.to_owned(),
+                name: param.clone(),
+            }));
+        }
+
+        match self {
+            Callable::Builtin(f, ..) => {
+                let host = env.borrow().host.clone();
+                let res = f(&host, args)?;
+                host.check_size(&res)?;
+                Ok(res)
+            }
+            Callable::Native(f) => {
+                let res = f.call(args)?;
+                env.borrow().host.check_size(&res)?;
+                Ok(res)
+            }
+            Callable::UserDefined { params, body, .. } => {
+                let local = Env::extend(env);
+                Env::bind_params(&local, name, params, args, named)?;
+
+                match Env::eval_internal(local, body) {
+                    Err(Internal::Return(val)) => Ok(val),
+                    res => res,
+                }
+            }
         }
     }
 }
@@ -51,198 +174,590 @@ pub struct Env {
     ctx_var: HashMap<String, Type>,
     ctx_fn: HashMap<String, Callable>,
     parent: Option<Rc<RefCell<Env>>>,
+    host: Rc<Host>,
 }
 
 impl Env {
+    /// Initializes a new global `Env` with every builtin available, printing
+    /// to stdout. See `EnvBuilder` to restrict what scripts can do.
     pub fn new() -> Rc<RefCell<Env>> {
-        let env = Env {
-            ctx_fn: prelude::init(),
-            ..Env::default()
-        };
-
-        Rc::new(RefCell::from(env))
+        EnvBuilder::new().build()
     }
 
     fn extend(env: Rc<RefCell<Env>>) -> Rc<RefCell<Env>> {
+        let host = env.borrow().host.clone();
         Rc::new(RefCell::from(Env {
             parent: Some(env),
+            host,
             ..Default::default()
         }))
     }
 
-    pub fn eval(env: Rc<RefCell<Env>>, ast: &AstNode) -> Type {
-        Env::eval_internal(env, ast).unwrap()
+    /// Returns a handle through which another thread can stop evaluations
+    /// in this `Env`.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.host.cancel_handle()
+    }
+
+    /// Makes a Rust closure callable from scripts as `name`, replacing any
+    /// function of the same name. Arguments are converted with `FromValue`
+    /// and the result with `IntoValue`; a wrong argument count or type, or an
+    /// `Err` result, stops the program with a `RuntimeError`. The closure may
+    /// capture host state, through a `RefCell` if it needs to mutate it.
+    ///
+    /// ```
+    /// use kotoba::{parser::Parser, runtime::*};
+    ///
+    /// let env = Env::new();
+    /// env.borrow_mut().register_fn("hypot", |a: f64, b: f64| a.hypot(b));
+    /// env.borrow_mut().register_fn("checked_div", |a: f64, b: f64| {
+    ///     if b == 0.0 {
+    ///         Err("division by zero")
+    ///     } else {
+    ///         Ok(a / b)
+    ///     }
+    /// });
+    ///
+    /// let ast = Parser::new("checked_div(hypot(3, 4), 2)").parse();
+    /// assert_eq!(Env::eval(env, &ast), Ok(Type::Number(2.5)));
+    /// ```
+    pub fn register_fn<Args>(&mut self, name: &str, f: impl NativeFn<Args>) {
+        let native = f.into_native(name);
+        self.ctx_fn
+            .insert(name.to_owned(), Callable::Native(native));
+    }
+
+    pub fn eval(env: Rc<RefCell<Env>>, ast: &AstNode) -> std::result::Result<Type, RuntimeError> {
+        env.borrow().host.reset();
+        Env::finish(Env::eval_internal(env, ast))
+    }
+
+    /// Calls the function `name` defined in `env` or its parents, typically
+    /// one defined by a program previously evaluated in a global `Env`. Each
+    /// call gets a fresh step budget, unless a native function called by the
+    /// program makes it, as the program's budget then still applies.
+    ///
+    /// ```
+    /// use kotoba::{parser::Parser, runtime::*};
+    ///
+    /// let env = Env::new();
+    /// let script = Parser::new("fn handle(request): ret \"hello, {request}\";").parse();
+    /// Env::eval(env.clone(), &script).unwrap();
+    ///
+    /// let response = Env::call(env, "handle", vec![Type::String("world".to_owned())]);
+    /// assert_eq!(response, Ok(Type::String("hello, world".to_owned())));
+    /// ```
+    pub fn call(
+        env: Rc<RefCell<Env>>,
+        name: &str,
+        args: Vec<Type>,
+    ) -> std::result::Result<Type, RuntimeError> {
+        env.borrow().host.reset();
+        match Env::call_fn(env, name, args, vec![]) {
+            Some(res) => Env::finish(res),
+            None => Err(RuntimeError::NoSuchFunction(name.to_owned())),
+        }
+    }
+
+    /// Returns the value of the variable `name` of this `Env`, ignoring its
+    /// parents.
+    pub fn get(&self, name: &str) -> Option<Type> {
+        self.ctx_var.get(name).cloned()
+    }
+
+    /// Returns the variables of this `Env`, ignoring its parents, sorted by
+    /// name.
+    pub fn variables(&self) -> Vec<(&str, &Type)> {
+        let mut variables: Vec<_> = self.ctx_var.iter().map(|(k, v)| (k.as_str(), v)).collect();
+        variables.sort_by_key(|&(name, _)| name);
+        variables
+    }
+
+    /// Returns the parameters of the functions that scripts defined in this
+    /// `Env`, ignoring its parents, sorted by name.
+    pub fn functions(&self) -> Vec<(&str, &[Param])> {
+        let mut functions: Vec<_> = self
+            .ctx_fn
+            .iter()
+            .filter_map(|(name, f)| match f {
+                Callable::UserDefined { params, .. } => Some((name.as_str(), &params[..])),
+                _ => None,
+            })
+            .collect();
+        functions.sort_by_key(|&(name, _)| name);
+        functions
+    }
+
+    /// Returns the types of the parameters and result of the function `name`
+    /// defined in this `Env` or its parents, whether scripts defined it or it
+    /// is a builtin or a registered native function.
+    pub fn signature(&self, name: &str) -> Option<Signature> {
+        match self.ctx_fn.get(name) {
+            Some(func) => Some(func.signature()),
+            None => self.parent.as_ref()?.borrow().signature(name),
+        }
+    }
+
+    /// Sets the variable `name` of this `Env`, creating it if needed.
+    pub fn set(&mut self, name: &str, value: impl IntoValue) {
+        self.ctx_var.insert(name.to_owned(), value.into_value());
+    }
+
+    fn finish(res: Result) -> std::result::Result<Type, RuntimeError> {
+        match res {
+            Ok(val) | Err(Internal::Return(val)) => Ok(val),
+            Err(Internal::Error(err)) => Err(err),
+        }
     }
 
     fn eval_internal(env: Rc<RefCell<Env>>, ast: &AstNode) -> Result {
-        match ast {
-            AstNode::Nil => Ok(Type::Nil),
-            AstNode::Number(n) => Ok(Type::Number(*n)),
-            AstNode::Boolean(b) => Ok(Type::Boolean(*b)),
-            AstNode::StringLiteral(s) => Ok(Type::String(s.clone())),
-            AstNode::Grouping(expr) => Env::eval_internal(env, expr),
-
-            AstNode::Identifier(id) => {
-                if let Some(val) = env.borrow().ctx_var.get(id) {
-                    return Ok(val.clone());
-                }
-                if let Some(ref p) = env.borrow().parent {
-                    return Env::eval_internal(p.clone(), &AstNode::Identifier(id.clone()));
+        env.borrow().host.step()?;
+
+        // deep recursion in the program is deep recursion here, which moves
+        // on to a new stack segment before overflowing the current one
+        stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT, || Env::eval_node(env, ast))
+    }
+
+    // Every arm that needs more than a couple of locals is evaluated in a
+    // function of its own: this one recurses for each nested node, so its
+    // stack frame must stay small.
+    fn eval_node(env: Rc<RefCell<Env>>, ast: &AstNode) -> Result {
+        match &ast.kind {
+            NodeKind::Nil => Ok(Type::Nil),
+            NodeKind::Number(n) => Ok(Type::Number(*n)),
+            NodeKind::Boolean(b) => Ok(Type::Boolean(*b)),
+            NodeKind::StringLiteral(s) => Ok(Type::String(s.clone())),
+            NodeKind::Interpolation(parts) => Env::eval_interpolation(env, parts),
+            NodeKind::Grouping(expr) => Env::eval_internal(env, expr),
+            NodeKind::List(items) => Env::eval_list(env, items),
+            NodeKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => Env::eval_method_call(env, receiver, method, args),
+            NodeKind::Identifier(id) => Env::eval_identifier(env, id),
+            NodeKind::FnCall {
+                identifier,
+                args,
+                named,
+            } => Env::eval_fn_call(env, identifier, args, named),
+            NodeKind::Program(stmts) => Env::eval_program(Env::extend(env), stmts),
+            NodeKind::ProgramRoot(stmts) => Env::eval_program(env, stmts),
+
+            NodeKind::Assignment {
+                identifier,
+                operand,
+                nonlocal,
+                ..
+            } => Env::eval_assignment(env, identifier, operand, *nonlocal),
+
+            NodeKind::IfStmt {
+                condition,
+                then_body,
+                else_body,
+            } => Env::eval_if(env, condition, then_body, else_body.as_deref()),
+
+            NodeKind::WhileStmt { condition, body } => {
+                while let Type::Boolean(true) = Env::eval_internal(env.clone(), condition)? {
+                    Env::eval_internal(env.clone(), body)?;
                 }
-                panic!("No such variable: {}", id);
+                Ok(Type::Nil)
             }
 
-            AstNode::FnCall { identifier, args } => {
-                if let Some(func) = env.borrow().ctx_fn.get(identifier) {
-                    let args_evaled = args
-                        .iter()
-                        .map(|a| Env::eval_internal(env.clone(), a).unwrap())
-                        .collect();
-                    return Ok(func.call(args_evaled));
-                }
-                if let Some(ref p) = env.borrow().parent {
-                    return Env::eval_internal(
-                        p.clone(),
-                        &AstNode::FnCall {
-                            identifier: identifier.clone(),
-                            args: args.clone(),
-                        },
-                    ); // FIXME: cloning ಠ_ಠ
-                }
+            NodeKind::FnStmt {
+                identifier,
+                params,
+                returns,
+                body,
+            } => {
+                Env::define_fn(&env, identifier, params, *returns, body);
                 Ok(Type::Nil)
             }
 
-            AstNode::Program(stmts) => {
-                let local = Env::extend(env);
+            NodeKind::MatchStmt { scrutinee, arms } => Env::eval_match(env, scrutinee, arms),
+            NodeKind::UnaryExpr { operator, operand } => Env::eval_unary(env, *operator, operand),
+            NodeKind::BinaryExpr { operator, lhs, rhs } => {
+                Env::eval_binary(env, *operator, lhs, rhs)
+            }
 
-                for s in stmts {
-                    match s {
-                        AstNode::RetStmt(expr) => {
-                            return Err(Internal::Return(
-                                Env::eval_internal(local.clone(), expr).unwrap(),
-                            ))
-                        }
-                        _ => {
-                            Env::eval_internal(local.clone(), s).unwrap();
-                        }
-                    }
-                }
+            NodeKind::RetStmt(_) => unreachable!(),
+        }
+    }
 
-                Ok(Type::Nil)
+    fn eval_assignment(
+        env: Rc<RefCell<Env>>,
+        identifier: &str,
+        operand: &AstNode,
+        nonlocal: bool,
+    ) -> Result {
+        let res = Env::eval_internal(env.clone(), operand)?;
+        if nonlocal {
+            Env::update_value(&env, identifier, res);
+        } else {
+            env.borrow_mut().ctx_var.insert(identifier.to_owned(), res);
+        }
+        Ok(Type::Nil)
+    }
+
+    fn eval_if(
+        env: Rc<RefCell<Env>>,
+        condition: &AstNode,
+        then_body: &AstNode,
+        else_body: Option<&AstNode>,
+    ) -> Result {
+        match Env::eval_internal(env.clone(), condition)? {
+            Type::Boolean(true) => Env::eval_internal(env, then_body),
+            Type::Boolean(false) => match else_body {
+                Some(prog) => Env::eval_internal(env, prog),
+                _ => Ok(Type::Nil),
+            },
+            other => Err(Env::non_boolean(&other)),
+        }
+    }
+
+    fn define_fn(
+        env: &Rc<RefCell<Env>>,
+        identifier: &str,
+        params: &[Param],
+        returns: Option<TypeName>,
+        body: &AstNode,
+    ) {
+        let func = Callable::UserDefined {
+            params: params.into(),
+            returns,
+            body: Rc::new(body.clone()),
+        };
+        env.borrow_mut().ctx_fn.insert(identifier.to_owned(), func);
+    }
+
+    fn eval_interpolation(env: Rc<RefCell<Env>>, parts: &[AstNode]) -> Result {
+        let mut out = String::new();
+        for p in parts {
+            out += &format!("{:#}", Env::eval_internal(env.clone(), p)?);
+        }
+
+        let res = Type::String(out);
+        env.borrow().host.check_size(&res)?;
+        Ok(res)
+    }
+
+    fn eval_list(env: Rc<RefCell<Env>>, items: &[AstNode]) -> Result {
+        let res = Type::List(
+            items
+                .iter()
+                .map(|i| Env::eval_internal(env.clone(), i))
+                .collect::<std::result::Result<_, _>>()?,
+        );
+        env.borrow().host.check_size(&res)?;
+        Ok(res)
+    }
+
+    fn eval_identifier(env: Rc<RefCell<Env>>, id: &str) -> Result {
+        let mut scope = env.clone();
+        loop {
+            if let Some(val) = scope.borrow().ctx_var.get(id) {
+                return Ok(val.clone());
+            }
+
+            let parent = scope.borrow().parent.clone();
+            match parent {
+                Some(p) => scope = p,
+                None => break,
             }
+        }
+
+        Err(Internal::Error(RuntimeError::UndefinedVariable {
+            name: id.to_owned(),
+            suggestions: error::suggestions(id, &Env::visible_names(&env)),
+        }))
+    }
+
+    fn eval_fn_call(
+        env: Rc<RefCell<Env>>,
+        identifier: &str,
+        args: &[AstNode],
+        named: &[(String, AstNode)],
+    ) -> Result {
+        let args_evaled = args
+            .iter()
+            .map(|a| Env::eval_internal(env.clone(), a))
+            .collect::<std::result::Result<_, _>>()?;
+        let named_evaled = named
+            .iter()
+            .map(|(name, a)| Ok((name.clone(), Env::eval_internal(env.clone(), a)?)))
+            .collect::<std::result::Result<_, Internal>>()?;
 
-            AstNode::ProgramRoot(stmts) => {
-                let mut ret = Type::Nil;
-
-                for s in stmts {
-                    match s {
-                        AstNode::RetStmt(expr) => {
-                            return Err(Internal::Return(
-                                Env::eval_internal(env.clone(), expr).unwrap(),
-                            ))
-                        }
-                        _ => {
-                            ret = Env::eval_internal(env.clone(), s).unwrap();
-                        }
-                    }
+        match Env::call_fn(env.clone(), identifier, args_evaled, named_evaled) {
+            Some(res) => res,
+            None => Err(Internal::Error(RuntimeError::UndefinedFunction {
+                name: identifier.to_owned(),
+                suggestions: error::suggestions(identifier, &Env::visible_names(&env)),
+            })),
+        }
+    }
+
+    /// Returns the names of the variables and functions visible from `env`,
+    /// to suggest in place of an undefined one.
+    fn visible_names(env: &Rc<RefCell<Env>>) -> Vec<String> {
+        let mut names = vec![];
+        let mut scope = Some(env.clone());
+        while let Some(env) = scope {
+            let env = env.borrow();
+            names.extend(env.ctx_var.keys().cloned());
+            names.extend(env.ctx_fn.keys().cloned());
+            scope = env.parent.clone();
+        }
+        names
+    }
+
+    /// Calls the function `identifier` visible from `env`, or returns `None`
+    /// if there is no such function. Fails if it doesn't take as many
+    /// arguments as given.
+    fn call_fn(
+        env: Rc<RefCell<Env>>,
+        identifier: &str,
+        args: Vec<Type>,
+        named: NamedArgs,
+    ) -> Option<Result> {
+        // walk up to the environment the function was defined in
+        let mut scope = env;
+        loop {
+            let func = scope.borrow().ctx_fn.get(identifier).cloned();
+            if let Some(func) = func {
+                let arity = func.arity();
+                if !arity.accepts_call(args.len(), named.len()) {
+                    return Some(Err(Internal::Error(RuntimeError::Arity {
+                        function: identifier.to_owned(),
+                        expected: arity,
+                        found: args.len() + named.len(),
+                    })));
                 }
 
-                Ok(ret)
+                let host = scope.borrow().host.clone();
+                if let Err(err) = host.enter_call() {
+                    return Some(Err(err.into()));
+                }
+                let res = func.call(identifier, scope, args, named);
+                host.exit_call();
+                return Some(res);
             }
 
-            AstNode::Assignment {
-                identifier,
-                operand,
-                nonlocal,
-            } => {
-                let res = Env::eval_internal(env.clone(), operand).unwrap();
-                if *nonlocal {
-                    Env::update_value(&env, identifier, res);
+            let parent = scope.borrow().parent.clone();
+            match parent {
+                Some(p) => scope = p,
+                None => return None,
+            }
+        }
+    }
+
+    fn eval_method_call(
+        env: Rc<RefCell<Env>>,
+        receiver: &AstNode,
+        method: &str,
+        args: &[AstNode],
+    ) -> Result {
+        let receiver = Env::eval_internal(env.clone(), receiver)?;
+        let args_evaled = args
+            .iter()
+            .map(|a| Env::eval_internal(env.clone(), a))
+            .collect::<std::result::Result<_, _>>()?;
+
+        let object = match receiver {
+            Type::Object(object) => object,
+            other => {
+                return Err(Internal::Error(RuntimeError::NoSuchMethod {
+                    type_name: other.type_name().to_owned(),
+                    method: method.to_owned(),
+                }))
+            }
+        };
+
+        let host = env.borrow().host.clone();
+        host.enter_call()?;
+        let res = object.call_method(method, args_evaled);
+        host.exit_call();
+
+        let res = res?;
+        host.check_size(&res)?;
+        Ok(res)
+    }
+
+    /// Binds the parameters of the function `function` in its local `env`:
+    /// positional arguments first, the rest of them into the rest parameter,
+    /// then named arguments, and finally defaults for what's left.
+    fn bind_params(
+        env: &Rc<RefCell<Env>>,
+        function: &str,
+        params: &[Param],
+        args: Vec<Type>,
+        named: NamedArgs,
+    ) -> std::result::Result<(), Internal> {
+        let mut args = args.into_iter();
+        let mut values = params
+            .iter()
+            .map(|p| {
+                if p.rest {
+                    Some(Type::List(args.by_ref().collect()))
                 } else {
-                    env.borrow_mut().ctx_var.insert(identifier.clone(), res);
+                    args.next()
                 }
-                Ok(Type::Nil)
+            })
+            .collect::<Vec<_>>();
+
+        for (name, value) in named {
+            let index = params
+                .iter()
+                .position(|p| p.name == name && !p.rest)
+                .ok_or_else(|| RuntimeError::UnknownParameter {
+                    function: function.to_owned(),
+                    name: name.clone(),
+                })?;
+            if values[index].replace(value).is_some() {
+                return Err(Internal::Error(RuntimeError::DuplicateArgument {
+                    function: function.to_owned(),
+                    name,
+                }));
             }
+        }
 
-            AstNode::IfStmt {
-                condition,
-                then_body,
-                else_body,
-            } => match Env::eval_internal(env.clone(), condition).unwrap() {
-                Type::Boolean(true) => Env::eval_internal(env, then_body),
-                Type::Boolean(false) => match else_body {
-                    Some(prog) => Env::eval_internal(env, prog),
-                    _ => Ok(Type::Nil),
-                },
-                _ => {
-                    println!("An if check must be a boolean expression");
-                    std::process::exit(5);
+        for (param, value) in params.iter().zip(values) {
+            let value = match (value, &param.default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => Env::eval_internal(env.clone(), default)?,
+                (None, None) => {
+                    return Err(Internal::Error(RuntimeError::MissingArgument {
+                        function: function.to_owned(),
+                        name: param.name.clone(),
+                    }))
                 }
-            },
+            };
+            env.borrow_mut().ctx_var.insert(param.name.clone(), value);
+        }
+        Ok(())
+    }
 
-            AstNode::WhileStmt { condition, body } => {
-                while let Type::Boolean(true) = Env::eval_internal(env.clone(), condition).unwrap()
-                {
-                    Env::eval_internal(env.clone(), body)?;
+    /// Evaluates the statements of a block in `env`, yielding the value of
+    /// the last one.
+    fn eval_program(env: Rc<RefCell<Env>>, stmts: &[AstNode]) -> Result {
+        let mut ret = Type::Nil;
+
+        for s in stmts {
+            match &s.kind {
+                NodeKind::RetStmt(expr) => {
+                    return Err(Internal::Return(Env::eval_internal(env, expr)?))
+                }
+                _ => {
+                    ret = Env::eval_internal(env.clone(), s)?;
                 }
-                Ok(Type::Nil)
             }
+        }
 
-            AstNode::FnStmt { .. } => Ok(Type::Nil), // temp
+        Ok(ret)
+    }
 
-            AstNode::UnaryExpr { operator, operand } => Ok(match (
-                operator,
-                Env::eval_internal(env, operand).unwrap(),
-            ) {
-                (Op::Minus, Type::Number(n)) => Type::Number(-n),
-                (Op::Bang, Type::Boolean(b)) => Type::Boolean(!b),
-                _ => {
-                    println!(
-                        "Unary operator {:?} can not be applied to type: {:?}",
-                        operator, operand
-                    );
-                    std::process::exit(2);
+    fn eval_match(env: Rc<RefCell<Env>>, scrutinee: &AstNode, arms: &[MatchArm]) -> Result {
+        let value = Env::eval_internal(env.clone(), scrutinee)?;
+
+        for arm in arms {
+            let mut bindings = vec![];
+            if !Env::match_pattern(&arm.pattern, &value, &mut bindings) {
+                continue;
+            }
+
+            let local = Env::extend(env.clone());
+            local.borrow_mut().ctx_var.extend(bindings);
+
+            if let Some(guard) = &arm.guard {
+                match Env::eval_internal(local.clone(), guard)? {
+                    Type::Boolean(true) => {}
+                    Type::Boolean(false) => continue,
+                    other => return Err(Env::non_boolean(&other)),
                 }
-            }),
-
-            AstNode::BinaryExpr { operator, lhs, rhs } => Ok(match (
-                operator,
-                Env::eval_internal(env.clone(), lhs).unwrap(),
-                Env::eval_internal(env, rhs).unwrap(),
-            ) {
-                (Op::EqualEqual, lhs, rhs) => Type::Boolean(lhs == rhs),
-                (Op::BangEqual, lhs, rhs) => Type::Boolean(lhs != rhs),
-                (Op::And, Type::Boolean(lhs), Type::Boolean(rhs)) => Type::Boolean(lhs && rhs),
-                (Op::Or, Type::Boolean(lhs), Type::Boolean(rhs)) => Type::Boolean(lhs || rhs),
-                (operator, Type::Number(lhsn), Type::Number(rhsn)) => match operator {
-                    Op::Plus => Type::Number(lhsn + rhsn),
-                    Op::Minus => Type::Number(lhsn - rhsn),
-                    Op::Star => Type::Number(lhsn * rhsn),
-                    Op::Slash => Type::Number(lhsn / rhsn),
-                    Op::Percent => Type::Number(lhsn % rhsn),
-                    Op::Greater => Type::Boolean(lhsn > rhsn),
-                    Op::GreaterEqual => Type::Boolean(lhsn >= rhsn),
-                    Op::Less => Type::Boolean(lhsn < rhsn),
-                    Op::LessEqual => Type::Boolean(lhsn <= rhsn),
-                    _ => {
-                        println!(
-                            "Operator {:?} can not be applied to types: {:?}, {:?}",
-                            operator, lhs, rhs
-                        );
-                        std::process::exit(3);
-                    }
-                },
-                (Op::Plus, Type::String(lhs), Type::String(rhs)) => Type::String(lhs + &rhs),
+            }
+
+            return Env::eval_internal(local, &arm.body);
+        }
+
+        Ok(Type::Nil)
+    }
+
+    fn eval_unary(env: Rc<RefCell<Env>>, operator: Op, operand: &AstNode) -> Result {
+        Ok(match (operator, Env::eval_internal(env, operand)?) {
+            (Op::Minus, Type::Number(n)) => Type::Number(-n),
+            (Op::Bang, Type::Boolean(b)) => Type::Boolean(!b),
+            (operator, value) => return Err(Env::invalid_operands(operator, &[&value])),
+        })
+    }
+
+    fn eval_binary(env: Rc<RefCell<Env>>, operator: Op, lhs: &AstNode, rhs: &AstNode) -> Result {
+        let lhs_val = Env::eval_internal(env.clone(), lhs)?;
+        let rhs_val = Env::eval_internal(env.clone(), rhs)?;
+
+        Ok(match (operator, lhs_val, rhs_val) {
+            (Op::EqualEqual, lhs, rhs) => Type::Boolean(lhs == rhs),
+            (Op::BangEqual, lhs, rhs) => Type::Boolean(lhs != rhs),
+            (Op::And, Type::Boolean(lhs), Type::Boolean(rhs)) => Type::Boolean(lhs && rhs),
+            (Op::Or, Type::Boolean(lhs), Type::Boolean(rhs)) => Type::Boolean(lhs || rhs),
+            (operator, Type::Number(lhsn), Type::Number(rhsn)) => match operator {
+                Op::Plus => Type::Number(lhsn + rhsn),
+                Op::Minus => Type::Number(lhsn - rhsn),
+                Op::Star => Type::Number(lhsn * rhsn),
+                Op::StarStar => Type::Number(lhsn.powf(rhsn)),
+                Op::Slash => Type::Number(lhsn / rhsn),
+                Op::TildeSlash => Type::Number((lhsn / rhsn).floor()),
+                Op::Percent => Type::Number(lhsn % rhsn),
+                Op::Greater => Type::Boolean(lhsn > rhsn),
+                Op::GreaterEqual => Type::Boolean(lhsn >= rhsn),
+                Op::Less => Type::Boolean(lhsn < rhsn),
+                Op::LessEqual => Type::Boolean(lhsn <= rhsn),
                 _ => {
-                    println!(
-                        "Operator {:?} can not be applied to types: {:?}, {:?}",
-                        operator, lhs, rhs
-                    );
-                    std::process::exit(3);
+                    let number = Type::Number(lhsn);
+                    return Err(Env::invalid_operands(operator, &[&number, &number]));
                 }
-            }),
+            },
+            (Op::Plus, Type::String(lhs), Type::String(rhs)) => {
+                env.borrow().host.check_string_len(lhs.len() + rhs.len())?;
+                Type::String(lhs + &rhs)
+            }
+            (Op::Greater, Type::String(lhs), Type::String(rhs)) => Type::Boolean(lhs > rhs),
+            (Op::GreaterEqual, Type::String(lhs), Type::String(rhs)) => Type::Boolean(lhs >= rhs),
+            (Op::Less, Type::String(lhs), Type::String(rhs)) => Type::Boolean(lhs < rhs),
+            (Op::LessEqual, Type::String(lhs), Type::String(rhs)) => Type::Boolean(lhs <= rhs),
+            (operator, lhs, rhs) => return Err(Env::invalid_operands(operator, &[&lhs, &rhs])),
+        })
+    }
+
+    fn invalid_operands(operator: Op, operands: &[&Type]) -> Internal {
+        let types = operands.iter().map(|o| o.type_name().to_owned()).collect();
+        RuntimeError::InvalidOperands { operator, types }.into()
+    }
 
-            AstNode::RetStmt(_) => unreachable!(),
+    fn non_boolean(found: &Type) -> Internal {
+        let found = found.type_name().to_owned();
+        RuntimeError::NonBooleanCondition { found }.into()
+    }
+
+    /// Checks whether `value` matches `pattern`, collecting the values bound
+    /// by the pattern into `bindings`.
+    fn match_pattern(pattern: &Pattern, value: &Type, bindings: &mut Vec<(String, Type)>) -> bool {
+        match (pattern, value) {
+            (Pattern::Wildcard, _) => true,
+            (Pattern::Binding(id), v) => {
+                bindings.push((id.clone(), v.clone()));
+                true
+            }
+            (Pattern::Number(p), Type::Number(v)) => p == v,
+            (Pattern::Boolean(p), Type::Boolean(v)) => p == v,
+            (Pattern::StringLiteral(p), Type::String(v)) => p == v,
+            (Pattern::Nil, Type::Nil) => true,
+            (Pattern::List(ps), Type::List(vs)) => {
+                ps.len() == vs.len()
+                    && ps
+                        .iter()
+                        .zip(vs)
+                        .all(|(p, v)| Env::match_pattern(p, v, bindings))
+            }
+            _ => false,
         }
     }
 
@@ -0,0 +1,83 @@
+use crate::parser::Param;
+use std::fmt;
+
+/// How many arguments a function takes: at least `min`, and at most `max`
+/// unless it is variadic.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arity {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    pub fn exactly(n: usize) -> Self {
+        Arity {
+            min: n,
+            max: Some(n),
+        }
+    }
+
+    /// Takes `min` arguments, and up to `max` with the optional ones.
+    pub fn between(min: usize, max: usize) -> Self {
+        Arity {
+            min,
+            max: Some(max),
+        }
+    }
+
+    /// Takes `min` arguments, and any number more.
+    pub fn at_least(min: usize) -> Self {
+        Arity { min, max: None }
+    }
+
+    /// Returns the arity of a function declared with `params`: parameters
+    /// with a default are optional, and a rest parameter makes it variadic.
+    pub fn of_params(params: &[Param]) -> Self {
+        let required = params
+            .iter()
+            .filter(|p| p.default.is_none() && !p.rest)
+            .count();
+        if params.last().is_some_and(|p| p.rest) {
+            Arity::at_least(required)
+        } else {
+            Arity::between(required, params.len())
+        }
+    }
+
+    pub fn accepts(&self, count: usize) -> bool {
+        self.min <= count && self.max.is_none_or(|max| count <= max)
+    }
+
+    /// Returns whether a call with `positional` and `named` arguments may
+    /// bind. With named arguments, only too many positional ones are ruled
+    /// out here, as binding them by name tells more precisely what's wrong.
+    pub fn accepts_call(&self, positional: usize, named: usize) -> bool {
+        if named == 0 {
+            self.accepts(positional)
+        } else {
+            self.max.is_none_or(|max| positional <= max)
+        }
+    }
+
+    /// Returns the arity left once the first argument is bound, as with the
+    /// receiver of a method.
+    pub(crate) fn without_first(self) -> Self {
+        Arity {
+            min: self.min.saturating_sub(1),
+            max: self.max.map(|max| max.saturating_sub(1)),
+        }
+    }
+}
+
+/// Arities are displayed as the number of arguments a function takes, such
+/// as "2 arguments", "1 to 2 arguments" or "at least 1 argument".
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (count, last) = match self.max {
+            Some(max) if max == self.min => (max.to_string(), max),
+            Some(max) => (format!("{} to {}", self.min, max), max),
+            None => (format!("at least {}", self.min), self.min),
+        };
+        write!(f, "{} argument{}", count, if last == 1 { "" } else { "s" })
+    }
+}
@@ -0,0 +1,197 @@
+use super::Arity;
+use crate::parser::Op;
+use std::{error, fmt};
+
+/// An error that stops the evaluation of a program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    /// The program ran into one of the `Limits` its `Env` was built with.
+    LimitExceeded(Limit),
+    /// The evaluation was stopped through a `CancelHandle`.
+    Cancelled,
+    /// A function was called with the wrong number of arguments.
+    Arity {
+        function: String,
+        expected: Arity,
+        found: usize,
+    },
+    /// An argument of a native function couldn't be converted to the type
+    /// of its parameter. `position` counts from 1.
+    ArgumentType {
+        function: String,
+        position: usize,
+        expected: String,
+        found: String,
+    },
+    /// The host called a function that isn't defined.
+    NoSuchFunction(String),
+    /// The program called a function that isn't defined. `suggestions` are
+    /// the visible names close enough to it to be what was meant.
+    UndefinedFunction {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    /// The program used a variable that isn't defined.
+    UndefinedVariable {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    /// A named argument doesn't match any parameter of the function.
+    UnknownParameter { function: String, name: String },
+    /// A parameter was given both a positional and a named argument, or two
+    /// named ones.
+    DuplicateArgument { function: String, name: String },
+    /// A parameter without a default was given no argument.
+    MissingArgument { function: String, name: String },
+    /// A method was called on a value that doesn't have it.
+    NoSuchMethod { type_name: String, method: String },
+    /// An operator was applied to values of types it doesn't support.
+    InvalidOperands { operator: Op, types: Vec<String> },
+    /// An `if` condition or a match guard isn't a boolean.
+    NonBooleanCondition { found: String },
+    /// A native function returned an error.
+    Native { function: String, message: String },
+}
+
+/// Which of the `Limits` was exceeded, along with its configured value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Steps(u64),
+    CallDepth(usize),
+    ValueSize(usize),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::LimitExceeded(Limit::Steps(n)) => {
+                write!(f, "exceeded the limit of {} evaluation steps", n)
+            }
+            RuntimeError::LimitExceeded(Limit::CallDepth(n)) => {
+                write!(f, "exceeded the maximum call depth of {}", n)
+            }
+            RuntimeError::LimitExceeded(Limit::ValueSize(n)) => {
+                write!(f, "exceeded the maximum value size of {} bytes", n)
+            }
+            RuntimeError::Cancelled => write!(f, "evaluation was cancelled"),
+            RuntimeError::Arity {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` takes {} but {} {} given",
+                function,
+                expected,
+                found,
+                if *found == 1 { "was" } else { "were" }
+            ),
+            RuntimeError::ArgumentType {
+                function,
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "argument {} of `{}` must be {}, found {}",
+                position, function, expected, found
+            ),
+            RuntimeError::Native { function, message } => write!(f, "{}: {}", function, message),
+            RuntimeError::NoSuchFunction(name) => write!(f, "no function named `{}`", name),
+            RuntimeError::UndefinedFunction { name, suggestions } => {
+                write!(f, "undefined function `{}`", name)?;
+                did_you_mean(f, suggestions)
+            }
+            RuntimeError::UndefinedVariable { name, suggestions } => {
+                write!(f, "undefined variable `{}`", name)?;
+                did_you_mean(f, suggestions)
+            }
+            RuntimeError::UnknownParameter { function, name } => {
+                write!(f, "`{}` has no parameter named `{}`", function, name)
+            }
+            RuntimeError::DuplicateArgument { function, name } => {
+                write!(
+                    f,
+                    "`{}` got more than one argument for `{}`",
+                    function, name
+                )
+            }
+            RuntimeError::MissingArgument { function, name } => {
+                write!(f, "`{}` is missing an argument for `{}`", function, name)
+            }
+            RuntimeError::NoSuchMethod { type_name, method } => {
+                write!(f, "{} has no method `{}`", type_name, method)
+            }
+            RuntimeError::InvalidOperands { operator, types } => write!(
+                f,
+                "`{}` can't be applied to {}",
+                operator,
+                types.join(" and ")
+            ),
+            RuntimeError::NonBooleanCondition { found } => {
+                write!(f, "condition must be a boolean, found {}", found)
+            }
+        }
+    }
+}
+
+impl error::Error for RuntimeError {}
+
+fn did_you_mean(f: &mut fmt::Formatter, suggestions: &[String]) -> fmt::Result {
+    let quoted = suggestions
+        .iter()
+        .map(|s| format!("`{}`", s))
+        .collect::<Vec<_>>();
+    match quoted.split_last() {
+        None => Ok(()),
+        Some((last, [])) => write!(f, "; did you mean {}?", last),
+        Some((last, rest)) => write!(f, "; did you mean {} or {}?", rest.join(", "), last),
+    }
+}
+
+/// The most suggestions an error lists.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns the names among `candidates` that `name` is likely a typo of,
+/// closest first: those within an edit distance of a third of its length,
+/// and at least one, but less than its length, as any name is that close to
+/// a one-letter one.
+pub(crate) fn suggestions(name: &str, candidates: &[String]) -> Vec<String> {
+    let max_distance = (name.len() / 3).max(1).min(name.len().saturating_sub(1));
+    let mut close = candidates
+        .iter()
+        .filter(|c| *c != name)
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .collect::<Vec<_>>();
+    close.sort_unstable();
+    close.dedup();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c.clone())
+        .collect()
+}
+
+/// Returns the number of insertions, deletions, substitutions and swaps of
+/// adjacent bytes needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    // rows of the distances between prefixes of `a` and of `b`
+    let mut before = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let substitution = previous[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            current[j] = substitution.min(previous[j] + 1).min(current[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, current.clone());
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,279 @@
+use super::{prelude, Env, Limit, RuntimeError, Type};
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    io::{self, Write},
+    mem,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+type Result = std::result::Result<(), RuntimeError>;
+
+/// Groups of builtins that an embedder may withhold from scripts. Calling a
+/// withheld builtin is an error, as with any undefined function.
+#[derive(Debug, Clone, Copy)]
+pub struct Capabilities {
+    /// Printing: `print` and `println`.
+    pub io: bool,
+    /// Reading the standard input: `input` and `read_line`.
+    pub stdin: bool,
+    /// Reading and writing files: `read_file`, `write_file`, `append_file`,
+    /// `exists` and `list_dir`.
+    pub fs: bool,
+    /// The system clock: `time` and `sleep`.
+    pub time: bool,
+    /// The host process: `args`, `getenv` and `exit`.
+    pub process: bool,
+}
+
+impl Capabilities {
+    /// Withholds every optional group, leaving only the pure builtins.
+    pub fn none() -> Self {
+        Capabilities {
+            io: false,
+            stdin: false,
+            fs: false,
+            time: false,
+            process: false,
+        }
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities {
+            io: true,
+            stdin: true,
+            fs: true,
+            time: true,
+            process: true,
+        }
+    }
+}
+
+/// Bounds on the resources a program may use, `None` meaning unbounded.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Evaluation steps (roughly, AST nodes evaluated) per `Env::eval`.
+    pub max_steps: Option<u64>,
+    /// Nesting of function calls. The interpreter grows its stack on the
+    /// heap as programs recurse, so without a bound, runaway recursion only
+    /// stops once memory runs out.
+    pub max_call_depth: Option<usize>,
+    /// Approximate size in bytes of any single string or list. Builtins
+    /// check the length of the strings they build before allocating them.
+    pub max_value_size: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_steps: None,
+            max_call_depth: Some(10_000),
+            max_value_size: None,
+        }
+    }
+}
+
+/// Stops an evaluation from another thread, making it fail with
+/// `RuntimeError::Cancelled`, even while it sleeps or waits for input. The
+/// handle is re-armed when the next `Env::eval` or `Env::call` starts, so a
+/// cancellation only ever stops the evaluation in progress.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn rearm(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// The parts of the outside world that builtins get to see, and the
+/// bookkeeping for the limits imposed on the program.
+pub(crate) struct Host {
+    pub(crate) output: RefCell<Box<dyn Write>>,
+    pub(crate) args: Vec<String>,
+    /// State of the pseudorandom number generator of `random`, seeded from
+    /// the clock unless the program calls `seed`.
+    pub(crate) rng: Cell<u64>,
+    limits: Limits,
+    cancel: CancelHandle,
+    steps: Cell<u64>,
+    call_depth: Cell<usize>,
+}
+
+impl Default for Host {
+    fn default() -> Self {
+        Host {
+            output: RefCell::new(Box::new(io::stdout())),
+            args: vec![],
+            rng: Cell::new(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_nanos() as u64)
+                    .unwrap_or_default(),
+            ),
+            limits: Limits::default(),
+            cancel: CancelHandle::default(),
+            steps: Cell::new(0),
+            call_depth: Cell::new(0),
+        }
+    }
+}
+
+impl fmt::Debug for Host {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Host")
+            .field("args", &self.args)
+            .field("limits", &self.limits)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Host {
+    pub(crate) fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Resets the step budget and the cancel handle, at the start of an
+    /// evaluation. An evaluation started by a native function that the
+    /// program called, which is within a call, goes on with those of the
+    /// program instead.
+    pub(crate) fn reset(&self) {
+        if self.call_depth.get() > 0 {
+            return;
+        }
+        self.steps.set(0);
+        self.call_depth.set(0);
+        self.cancel.rearm();
+    }
+
+    /// Fails if the evaluation was cancelled, for builtins to check while
+    /// they wait.
+    pub(crate) fn check_cancelled(&self) -> Result {
+        if self.cancel.is_cancelled() {
+            return Err(RuntimeError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Accounts for one evaluation step.
+    pub(crate) fn step(&self) -> Result {
+        self.check_cancelled()?;
+
+        self.steps.set(self.steps.get() + 1);
+        match self.limits.max_steps {
+            Some(max) if self.steps.get() > max => {
+                Err(RuntimeError::LimitExceeded(Limit::Steps(max)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Accounts for entering a function call, which must be paired with
+    /// `exit_call` if successful.
+    pub(crate) fn enter_call(&self) -> Result {
+        match self.limits.max_call_depth {
+            Some(max) if self.call_depth.get() >= max => {
+                Err(RuntimeError::LimitExceeded(Limit::CallDepth(max)))
+            }
+            _ => {
+                self.call_depth.set(self.call_depth.get() + 1);
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn exit_call(&self) {
+        self.call_depth.set(self.call_depth.get() - 1);
+    }
+
+    /// Checks a newly created value against the value size limit.
+    pub(crate) fn check_size(&self, value: &Type) -> Result {
+        self.check_projected_size(size_of(value))
+    }
+
+    /// Checks that a string of `len` bytes would be within the value size
+    /// limit, before it gets built.
+    pub(crate) fn check_string_len(&self, len: usize) -> Result {
+        self.check_projected_size(mem::size_of::<Type>().saturating_add(len))
+    }
+
+    fn check_projected_size(&self, size: usize) -> Result {
+        match self.limits.max_value_size {
+            Some(max) if size > max => Err(RuntimeError::LimitExceeded(Limit::ValueSize(max))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Approximates the number of bytes taken up by `value`.
+fn size_of(value: &Type) -> usize {
+    mem::size_of::<Type>()
+        + match value {
+            Type::String(s) => s.len(),
+            Type::List(items) => items.iter().map(size_of).sum(),
+            _ => 0,
+        }
+}
+
+/// Configures a new global `Env`: which builtins it exposes, where `print`
+/// and `println` write to, what `args` returns, and the resources programs
+/// may use.
+#[derive(Default)]
+pub struct EnvBuilder {
+    capabilities: Capabilities,
+    host: Host,
+}
+
+impl EnvBuilder {
+    pub fn new() -> Self {
+        EnvBuilder::default()
+    }
+
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Redirects the output of `print` and `println` to `sink`.
+    pub fn output(mut self, sink: impl Write + 'static) -> Self {
+        self.host.output = RefCell::new(Box::new(sink));
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.host.limits = limits;
+        self
+    }
+
+    /// Sets the arguments returned by the `args` builtin.
+    pub fn args(mut self, args: Vec<String>) -> Self {
+        self.host.args = args;
+        self
+    }
+
+    pub fn build(self) -> Rc<RefCell<Env>> {
+        let env = Env {
+            ctx_var: prelude::constants(),
+            ctx_fn: prelude::init(self.capabilities),
+            host: Rc::new(self.host),
+            ..Env::default()
+        };
+
+        Rc::new(RefCell::from(env))
+    }
+}
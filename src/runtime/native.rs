@@ -0,0 +1,363 @@
+use super::{Arity, RuntimeError, Signature, Type};
+use crate::parser::TypeName;
+use std::{fmt, rc::Rc};
+
+/// Conversion of a script value into a Rust argument of a native function.
+pub trait FromValue: Sized {
+    /// Describes the accepted values in error messages, e.g. "a number".
+    fn expected() -> String;
+
+    /// Returns `None` if `value` isn't one of the accepted values.
+    fn from_value(value: Type) -> Option<Self>;
+
+    /// The type the `checker` expects for the argument.
+    fn accepted_type() -> TypeName {
+        TypeName::Any
+    }
+}
+
+/// Conversion of the result of a native function into a script value.
+pub trait IntoValue {
+    fn into_value(self) -> Type;
+
+    /// The type the `checker` gives to the result.
+    fn result_type() -> TypeName {
+        TypeName::Any
+    }
+}
+
+impl FromValue for Type {
+    fn expected() -> String {
+        "any value".to_owned()
+    }
+
+    fn from_value(value: Type) -> Option<Self> {
+        Some(value)
+    }
+}
+
+impl FromValue for f64 {
+    fn expected() -> String {
+        "a number".to_owned()
+    }
+
+    fn from_value(value: Type) -> Option<Self> {
+        match value {
+            Type::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    fn accepted_type() -> TypeName {
+        TypeName::Num
+    }
+}
+
+/// Accepts only numbers without a fractional part that fit an `i64`.
+impl FromValue for i64 {
+    fn expected() -> String {
+        "an integer".to_owned()
+    }
+
+    fn from_value(value: Type) -> Option<Self> {
+        match value {
+            Type::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => Some(n as i64),
+            _ => None,
+        }
+    }
+
+    fn accepted_type() -> TypeName {
+        TypeName::Num
+    }
+}
+
+impl FromValue for bool {
+    fn expected() -> String {
+        "a boolean".to_owned()
+    }
+
+    fn from_value(value: Type) -> Option<Self> {
+        match value {
+            Type::Boolean(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    fn accepted_type() -> TypeName {
+        TypeName::Bool
+    }
+}
+
+impl FromValue for String {
+    fn expected() -> String {
+        "a string".to_owned()
+    }
+
+    fn from_value(value: Type) -> Option<Self> {
+        match value {
+            Type::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn accepted_type() -> TypeName {
+        TypeName::Str
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn expected() -> String {
+        format!("a list, each item being {}", T::expected())
+    }
+
+    fn from_value(value: Type) -> Option<Self> {
+        match value {
+            Type::List(items) => items.into_iter().map(T::from_value).collect(),
+            _ => None,
+        }
+    }
+
+    fn accepted_type() -> TypeName {
+        TypeName::List
+    }
+}
+
+/// `nil` converts to `None`.
+impl<T: FromValue> FromValue for Option<T> {
+    fn expected() -> String {
+        format!("{} or nil", T::expected())
+    }
+
+    fn from_value(value: Type) -> Option<Self> {
+        match value {
+            Type::Nil => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl IntoValue for Type {
+    fn into_value(self) -> Type {
+        self
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Type {
+        Type::Number(self)
+    }
+
+    fn result_type() -> TypeName {
+        TypeName::Num
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Type {
+        Type::Number(self as f64)
+    }
+
+    fn result_type() -> TypeName {
+        TypeName::Num
+    }
+}
+
+impl IntoValue for usize {
+    fn into_value(self) -> Type {
+        Type::Number(self as f64)
+    }
+
+    fn result_type() -> TypeName {
+        TypeName::Num
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Type {
+        Type::Boolean(self)
+    }
+
+    fn result_type() -> TypeName {
+        TypeName::Bool
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Type {
+        Type::String(self)
+    }
+
+    fn result_type() -> TypeName {
+        TypeName::Str
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Type {
+        Type::String(self.to_owned())
+    }
+
+    fn result_type() -> TypeName {
+        TypeName::Str
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Type {
+        Type::Nil
+    }
+
+    fn result_type() -> TypeName {
+        TypeName::Nil
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Type {
+        Type::List(self.into_iter().map(IntoValue::into_value).collect())
+    }
+
+    fn result_type() -> TypeName {
+        TypeName::List
+    }
+}
+
+/// `None` converts to `nil`.
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Type {
+        self.map_or(Type::Nil, IntoValue::into_value)
+    }
+}
+
+/// What a native function may return: either a value, or a `Result` whose
+/// error stops the program with `RuntimeError::Native`.
+pub trait IntoNativeResult {
+    fn into_native_result(self) -> Result<Type, String>;
+
+    /// The type the `checker` gives to the value.
+    fn result_type() -> TypeName {
+        TypeName::Any
+    }
+}
+
+impl<T: IntoValue> IntoNativeResult for T {
+    fn into_native_result(self) -> Result<Type, String> {
+        Ok(self.into_value())
+    }
+
+    fn result_type() -> TypeName {
+        <T as IntoValue>::result_type()
+    }
+}
+
+impl<T: IntoValue, E: fmt::Display> IntoNativeResult for Result<T, E> {
+    fn into_native_result(self) -> Result<Type, String> {
+        self.map(IntoValue::into_value).map_err(|e| e.to_string())
+    }
+
+    fn result_type() -> TypeName {
+        <T as IntoValue>::result_type()
+    }
+}
+
+/// A Rust closure that can be registered with `Env::register_fn`. `Args` is
+/// the tuple of its parameter types, which lets closures of different
+/// arities implement the trait.
+pub trait NativeFn<Args>: 'static {
+    fn into_native(self, name: &str) -> Native;
+}
+
+type Erased = dyn Fn(Vec<Type>) -> Result<Type, RuntimeError>;
+
+/// A native function, with its arguments and result converted to and from
+/// script values. It can only be created through `NativeFn`.
+#[derive(Clone)]
+pub struct Native {
+    f: Rc<Erased>,
+    arity: Arity,
+    signature: Signature,
+}
+
+impl Native {
+    pub(crate) fn call(&self, args: Vec<Type>) -> Result<Type, RuntimeError> {
+        (self.f)(args)
+    }
+
+    pub fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    /// Returns the types of the parameters and result, from their Rust
+    /// types.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Native")
+    }
+}
+
+/// Converts the argument at `position` (counting from 1) of `function`.
+fn convert<T: FromValue>(function: &str, position: usize, value: Type) -> Result<T, RuntimeError> {
+    let found = value.type_name().to_owned();
+    T::from_value(value).ok_or_else(|| RuntimeError::ArgumentType {
+        function: function.to_owned(),
+        position,
+        expected: T::expected(),
+        found,
+    })
+}
+
+macro_rules! native_fn {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> NativeFn<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: IntoNativeResult,
+            $($arg: FromValue),*
+        {
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn into_native(self, name: &str) -> Native {
+                let name = name.to_owned();
+                let arity = Arity::exactly(<[&str]>::len(&[$(stringify!($arg)),*]));
+                let f = Rc::new(move |args: Vec<Type>| {
+                    if !arity.accepts(args.len()) {
+                        return Err(RuntimeError::Arity {
+                            function: name.clone(),
+                            expected: arity,
+                            found: args.len(),
+                        });
+                    }
+
+                    let mut args = args.into_iter();
+                    let mut position = 0;
+                    $(
+                        position += 1;
+                        let $arg = convert::<$arg>(&name, position, args.next().unwrap())?;
+                    )*
+
+                    self($($arg),*).into_native_result().map_err(|message| {
+                        RuntimeError::Native {
+                            function: name.clone(),
+                            message,
+                        }
+                    })
+                });
+                let params = [$(<$arg as FromValue>::accepted_type()),*];
+                let signature = Signature::unnamed(arity, &params, R::result_type());
+                Native { f, arity, signature }
+            }
+        }
+    };
+}
+
+native_fn!();
+native_fn!(A);
+native_fn!(A, B);
+native_fn!(A, B, C);
+native_fn!(A, B, C, D);
+native_fn!(A, B, C, D, E);
+native_fn!(A, B, C, D, E, G);
@@ -0,0 +1,233 @@
+use super::{FromValue, Native, NativeFn, RuntimeError, Type};
+use std::{
+    any::{self, Any},
+    collections::HashMap,
+    fmt,
+    marker::PhantomData,
+    rc::Rc,
+};
+
+/// A Rust value handed to scripts, which can only pass it around, print it,
+/// compare it, and call the methods of its `Class`.
+#[derive(Clone)]
+pub struct Object {
+    value: Rc<dyn Any>,
+    class: Rc<ClassInfo>,
+}
+
+type DisplayHook = dyn Fn(&dyn Any) -> String;
+type EqualityHook = dyn Fn(&dyn Any, &dyn Any) -> bool;
+
+struct ClassInfo {
+    name: String,
+    methods: HashMap<String, Native>,
+    display: Option<Box<DisplayHook>>,
+    equality: Option<Box<EqualityHook>>,
+}
+
+impl Object {
+    /// The name of the object's class, which is what `type_of` returns.
+    pub fn class_name(&self) -> &str {
+        &self.class.name
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    pub fn downcast<T: Any>(&self) -> Option<Rc<T>> {
+        self.value.clone().downcast().ok()
+    }
+
+    /// Calls `method` with the object itself as the first argument. Errors
+    /// about the arguments are adjusted to leave the receiver out.
+    pub(crate) fn call_method(&self, method: &str, args: Vec<Type>) -> Result<Type, RuntimeError> {
+        let native = match self.class.methods.get(method) {
+            Some(native) => native,
+            None => {
+                return Err(RuntimeError::NoSuchMethod {
+                    type_name: self.class.name.clone(),
+                    method: method.to_owned(),
+                })
+            }
+        };
+
+        let mut all_args = Vec::with_capacity(args.len() + 1);
+        all_args.push(Type::Object(self.clone()));
+        all_args.extend(args);
+
+        native.call(all_args).map_err(|err| match err {
+            RuntimeError::Arity {
+                function,
+                expected,
+                found,
+            } => RuntimeError::Arity {
+                function,
+                expected: expected.without_first(),
+                found: found - 1,
+            },
+            RuntimeError::ArgumentType {
+                function,
+                position,
+                expected,
+                found,
+            } => RuntimeError::ArgumentType {
+                function,
+                position: position - 1,
+                expected,
+                found,
+            },
+            err => err,
+        })
+    }
+}
+
+/// Objects are displayed through their class's `display` hook, if any.
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.class.display {
+            Some(display) => f.write_str(&display(&*self.value)),
+            None => write!(f, "<{}>", self.class.name),
+        }
+    }
+}
+
+impl fmt::Debug for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Object({})", self)
+    }
+}
+
+/// Objects of different classes are never equal. Objects of the same class
+/// are compared with its `equality` hook, or else are equal only if they
+/// are the same object.
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        if !Rc::ptr_eq(&self.class, &other.class) {
+            return false;
+        }
+
+        match &self.class.equality {
+            Some(equality) => equality(&*self.value, &*other.value),
+            None => Rc::ptr_eq(&self.value, &other.value),
+        }
+    }
+}
+
+/// Methods receive their object as an `Rc<T>`, and so can native functions.
+impl<T: Any> FromValue for Rc<T> {
+    fn expected() -> String {
+        let name = any::type_name::<T>();
+        format!(
+            "an object of type {}",
+            name.rsplit("::").next().unwrap_or(name)
+        )
+    }
+
+    fn from_value(value: Type) -> Option<Self> {
+        match value {
+            Type::Object(object) => object.downcast(),
+            _ => None,
+        }
+    }
+}
+
+/// Describes how scripts see values of type `T`: the name of their type, the
+/// methods they can call on them, and how they are displayed and compared.
+///
+/// ```
+/// use kotoba::{parser::Parser, runtime::*};
+/// use std::{cell::Cell, rc::Rc};
+///
+/// #[derive(Default)]
+/// struct Counter(Cell<f64>);
+///
+/// let counters = Class::new("Counter")
+///     .method("incr", |c: Rc<Counter>, by: f64| c.0.set(c.0.get() + by))
+///     .method("get", |c: Rc<Counter>| c.0.get())
+///     .display(|c: &Counter| format!("Counter({})", c.0.get()))
+///     .build();
+///
+/// let env = Env::new();
+/// env.borrow_mut().register_fn("new_counter", move || counters.wrap(Counter::default()));
+///
+/// let ast = Parser::new("c = new_counter(), c.incr(2), c.incr(3), \"{c}\"").parse();
+/// assert_eq!(Env::eval(env, &ast), Ok(Type::String("Counter(5)".to_owned())));
+/// ```
+pub struct Class<T> {
+    info: ClassInfo,
+    marker: PhantomData<T>,
+}
+
+impl<T: Any> Class<T> {
+    pub fn new(name: &str) -> Self {
+        Class {
+            info: ClassInfo {
+                name: name.to_owned(),
+                methods: HashMap::new(),
+                display: None,
+                equality: None,
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// Adds a method, implemented by a closure whose first parameter is the
+    /// object, as an `Rc<T>`.
+    pub fn method<Args>(mut self, name: &str, f: impl NativeFn<Args>) -> Self {
+        let native = f.into_native(&format!("{}.{}", self.info.name, name));
+        self.info.methods.insert(name.to_owned(), native);
+        self
+    }
+
+    pub fn display(mut self, f: impl Fn(&T) -> String + 'static) -> Self {
+        self.info.display = Some(Box::new(move |value| f(downcast(value))));
+        self
+    }
+
+    pub fn equality(mut self, f: impl Fn(&T, &T) -> bool + 'static) -> Self {
+        self.info.equality = Some(Box::new(move |a, b| f(downcast(a), downcast(b))));
+        self
+    }
+
+    pub fn build(self) -> ClassHandle<T> {
+        ClassHandle {
+            info: Rc::new(self.info),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Only called on values of the class's own type.
+fn downcast<T: Any>(value: &dyn Any) -> &T {
+    value.downcast_ref().unwrap()
+}
+
+/// Wraps Rust values into objects of a `Class`.
+pub struct ClassHandle<T> {
+    info: Rc<ClassInfo>,
+    marker: PhantomData<T>,
+}
+
+impl<T: Any> ClassHandle<T> {
+    pub fn wrap(&self, value: T) -> Type {
+        self.wrap_rc(Rc::new(value))
+    }
+
+    /// Wraps a value the host keeps a reference to.
+    pub fn wrap_rc(&self, value: Rc<T>) -> Type {
+        Type::Object(Object {
+            value,
+            class: self.info.clone(),
+        })
+    }
+}
+
+impl<T> Clone for ClassHandle<T> {
+    fn clone(&self) -> Self {
+        ClassHandle {
+            info: self.info.clone(),
+            marker: PhantomData,
+        }
+    }
+}
@@ -1,49 +1,214 @@
+/// Declares builtins, each as `name(args: arity) [param types] -> result type`
+/// followed by its body, the parameter types being those the `checker`
+/// expects. The last parameter type of a variadic applies to every argument
+/// past the others.
 macro_rules! prelude {
-    ($($name:ident($args:ident) $body:block)*) => {
+    (
+        with $host:ident;
+        $(
+            $name:ident($args:ident: $($arity:tt)+) [$($param:ident),*] -> $returns:ident
+            $body:block
+        )*
+    ) => {
         use std::collections::HashMap;
-        use super::{Type, Callable};
+        use crate::{
+            parser::TypeName,
+            runtime::{Arity, Type, Callable, RuntimeError, host::Host},
+        };
 
-        pub(super) fn init() -> HashMap<String, Callable> {
+        pub(super) fn builtins() -> HashMap<String, Callable> {
             hashmap!{
-                $(stringify!($name).to_owned() => Callable::Builtin($name)),*
+                $(stringify!($name).to_owned() => Callable::Builtin(
+                    $name,
+                    arity!($($arity)+),
+                    &[$(type_name!($param)),*],
+                    type_name!($returns),
+                )),*
             }
         }
 
-        $(pub(super) fn $name($args: Vec<Type>) -> Type $body)*
+        $(
+            #[allow(unused_variables)]
+            pub(super) fn $name($host: &Host, $args: Vec<Type>) -> Result<Type, RuntimeError> $body
+        )*
+    };
+    (
+        $(
+            $name:ident($args:ident: $($arity:tt)+) [$($param:ident),*] -> $returns:ident
+            $body:block
+        )*
+    ) => {
+        prelude!{ with _host; $($name($args: $($arity)+) [$($param),*] -> $returns $body)* }
     };
 }
 
-prelude!{
-    print(args) {
-        for a in args {
-            let out = match a {
-                Type::String(s) => s,
-                _ => a.to_string(),
-            };
-            print!("{}", out);
-        }
+/// Declares how many arguments a builtin takes, like a range pattern: `1`,
+/// `1..=2`, or `1..` for variadics.
+macro_rules! arity {
+    ($n:literal) => {
+        Arity::exactly($n)
+    };
+    ($min:literal..=$max:literal) => {
+        Arity::between($min, $max)
+    };
+    ($min:literal..) => {
+        Arity::at_least($min)
+    };
+}
 
-        Type::Nil
-    }
+/// Names a `TypeName` by its annotation, such as `num`.
+macro_rules! type_name {
+    (num) => {
+        TypeName::Num
+    };
+    (str) => {
+        TypeName::Str
+    };
+    (bool) => {
+        TypeName::Bool
+    };
+    (list) => {
+        TypeName::List
+    };
+    (nil) => {
+        TypeName::Nil
+    };
+    (any) => {
+        TypeName::Any
+    };
+}
+
+mod convert;
+mod fs;
+mod io;
+#[cfg(feature = "serde")]
+mod json;
+mod math;
+mod process;
+mod stdin;
+mod string;
+mod time;
 
-    println(args) {
-        print(args);
-        println!();
+use super::{Arity, Callable, Capabilities, RuntimeError, Signature, Type};
+use std::{collections::HashMap, time::Duration};
 
-        Type::Nil
+/// How often builtins that wait check whether the evaluation was cancelled.
+const CANCEL_POLL: Duration = Duration::from_millis(10);
+
+pub(super) fn init(capabilities: Capabilities) -> HashMap<String, Callable> {
+    let mut builtins = string::builtins();
+    builtins.extend(math::builtins());
+    builtins.extend(convert::builtins());
+    #[cfg(feature = "serde")]
+    builtins.extend(json::builtins());
+
+    if capabilities.io {
+        builtins.extend(io::builtins());
+    }
+    if capabilities.stdin {
+        builtins.extend(stdin::builtins());
+    }
+    if capabilities.fs {
+        builtins.extend(fs::builtins());
+    }
+    if capabilities.time {
+        builtins.extend(time::builtins());
+    }
+    if capabilities.process {
+        builtins.extend(process::builtins());
     }
 
-    add_two(args) {
-        match args.as_slice() {
-            [Type::Number(left), Type::Number(right)] => Type::Number(left + right),
-            _ => panic!(),
-        }
+    builtins
+}
+
+pub(super) fn constants() -> HashMap<String, Type> {
+    math::constants()
+}
+
+/// Returns the sorted names of the builtin functions, with every capability
+/// granted.
+pub fn builtin_functions() -> Vec<String> {
+    let mut names: Vec<_> = init(Capabilities::default()).into_keys().collect();
+    names.sort();
+    names
+}
+
+/// Returns how many arguments the builtin function `name` takes, or `None`
+/// if there is no such builtin.
+///
+/// ```
+/// use kotoba::runtime::{builtin_arity, Arity};
+///
+/// assert_eq!(builtin_arity("pow"), Some(Arity::exactly(2)));
+/// assert_eq!(builtin_arity("max"), Some(Arity::at_least(1)));
+/// assert_eq!(builtin_arity("nope"), None);
+/// ```
+pub fn builtin_arity(name: &str) -> Option<Arity> {
+    init(Capabilities::default())
+        .get(name)
+        .map(|builtin| builtin.arity())
+}
+
+/// Returns the types of the parameters and result of the builtin function
+/// `name`, or `None` if there is no such builtin.
+///
+/// ```
+/// use kotoba::{parser::TypeName, runtime::builtin_signature};
+///
+/// let signature = builtin_signature("repeat").unwrap();
+/// assert_eq!(signature.params.len(), 2);
+/// assert_eq!(signature.params[1].1, TypeName::Num);
+/// assert_eq!(signature.returns, TypeName::Str);
+/// assert_eq!(builtin_signature("max").unwrap().rest, Some(TypeName::Num));
+/// ```
+pub fn builtin_signature(name: &str) -> Option<Signature> {
+    init(Capabilities::default())
+        .get(name)
+        .map(|builtin| builtin.signature())
+}
+
+/// Returns the sorted names of the builtin constants, such as `PI`.
+pub fn builtin_constants() -> Vec<String> {
+    let mut names: Vec<_> = constants().into_keys().collect();
+    names.sort();
+    names
+}
+
+/// Returns the error of a call to the builtin `name` with arguments it can't
+/// handle, which is about the first of `args` whose type isn't among those
+/// `expected` for its parameter. Each entry lists the type names a parameter
+/// takes, separated by `|`, or is `any`. The last entry applies to any
+/// arguments past it, as variadics take them.
+fn invalid_args(name: &str, args: &[Type], expected: &[&str]) -> RuntimeError {
+    let mismatch = args.iter().enumerate().find_map(|(i, arg)| {
+        let types = expected.get(i).or_else(|| expected.last())?;
+        let fits = *types == "any" || types.split('|').any(|t| t == arg.type_name());
+        (!fits).then_some((i, types))
+    });
+
+    match mismatch {
+        Some((i, types)) => RuntimeError::ArgumentType {
+            function: name.to_owned(),
+            position: i + 1,
+            expected: types
+                .split('|')
+                .map(|t| format!("a {}", t))
+                .collect::<Vec<_>>()
+                .join(" or "),
+            found: args[i].type_name().to_owned(),
+        },
+        None => RuntimeError::Native {
+            function: name.to_owned(),
+            message: "invalid arguments".to_owned(),
+        },
     }
+}
 
-    div(args) {
-        match args.as_slice() {
-            [Type::Number(q), Type::Number(n)] => Type::Boolean(n % q == 0.0),
-            _ => panic!(),
-        }
+/// Returns the error of a call to the builtin `name` with arguments of the
+/// right types but values it can't handle.
+fn invalid_value(name: &str, message: &str) -> RuntimeError {
+    RuntimeError::Native {
+        function: name.to_owned(),
+        message: message.to_owned(),
     }
 }
@@ -0,0 +1,97 @@
+//! Conversions between types and type introspection.
+//!
+//! | value        | `str`          | `num`          | `int`            | `bool`  |
+//! |--------------|----------------|----------------|------------------|---------|
+//! | `0`          | `"0"`          | `0`            | `0`              | `false` |
+//! | other number | `"1.5"`        | itself         | truncated        | `true`  |
+//! | `""`         | `""`           | `nil`          | `nil`            | `false` |
+//! | `" 2.5 "`    | itself         | `2.5`          | `2`              | `true`  |
+//! | other string | itself         | `nil`          | `nil`            | `true`  |
+//! | `true`       | `"true"`       | `1`            | `1`              | `true`  |
+//! | `false`      | `"false"`      | `0`            | `0`              | `false` |
+//! | `[]`         | `"[]"`         | `nil`          | `nil`            | `false` |
+//! | other list   | `"[1, \"a\"]"` | `nil`          | `nil`            | `true`  |
+//! | object       | its display    | `nil`          | `nil`            | `true`  |
+//! | `nil`        | `"nil"`        | `nil`          | `nil`            | `false` |
+//!
+//! A string converts to a number if, after trimming whitespace, it's a
+//! decimal number such as `12`, `-0.5` or `1e3`. Conversions that can't be
+//! performed yield `nil`, which can be checked for with `is_nil`. The
+//! `type_of` an object is the name of its class.
+
+use super::invalid_args;
+
+fn to_number(value: &Type) -> Option<f64> {
+    match value {
+        Type::Number(n) => Some(*n),
+        Type::Boolean(b) => Some(if *b { 1.0 } else { 0.0 }),
+        Type::String(s) => {
+            let s = s.trim();
+            // reject the `inf` and `NaN` spellings that `f64::from_str` allows
+            if s.bytes()
+                .any(|c| c.is_ascii_alphabetic() && c != b'e' && c != b'E')
+            {
+                return None;
+            }
+            s.parse().ok()
+        }
+        _ => None,
+    }
+}
+
+fn is_truthy(value: &Type) -> bool {
+    match value {
+        Type::Number(n) => *n != 0.0,
+        Type::Boolean(b) => *b,
+        Type::String(s) => !s.is_empty(),
+        Type::List(items) => !items.is_empty(),
+        Type::Object(_) => true,
+        Type::Nil => false,
+    }
+}
+
+prelude! {
+    str(args: 1) [any] -> str {
+        match args.as_slice() {
+            [value] => Ok(Type::String(format!("{:#}", value))),
+            _ => Err(invalid_args("str", &args, &["any"])),
+        }
+    }
+
+    num(args: 1) [any] -> any {
+        match args.as_slice() {
+            [value] => Ok(to_number(value).map(Type::Number).unwrap_or(Type::Nil)),
+            _ => Err(invalid_args("num", &args, &["any"])),
+        }
+    }
+
+    int(args: 1) [any] -> any {
+        match args.as_slice() {
+            [value] => Ok(to_number(value)
+                .map(|n| Type::Number(n.trunc()))
+                .unwrap_or(Type::Nil)),
+            _ => Err(invalid_args("int", &args, &["any"])),
+        }
+    }
+
+    bool(args: 1) [any] -> bool {
+        match args.as_slice() {
+            [value] => Ok(Type::Boolean(is_truthy(value))),
+            _ => Err(invalid_args("bool", &args, &["any"])),
+        }
+    }
+
+    type_of(args: 1) [any] -> str {
+        match args.as_slice() {
+            [value] => Ok(Type::String(value.type_name().to_owned())),
+            _ => Err(invalid_args("type_of", &args, &["any"])),
+        }
+    }
+
+    is_nil(args: 1) [any] -> bool {
+        match args.as_slice() {
+            [value] => Ok(Type::Boolean(*value == Type::Nil)),
+            _ => Err(invalid_args("is_nil", &args, &["any"])),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Filesystem access. Operations that fail yield `nil` (or `false` for the
+//! ones that return a boolean) rather than aborting the script.
+
+use super::invalid_args;
+use std::{fs, io::Write, path::Path};
+
+fn append(path: &str, contents: &str) -> bool {
+    fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .and_then(|mut f| f.write_all(contents.as_bytes()))
+        .is_ok()
+}
+
+prelude! {
+    read_file(args: 1) [str] -> any {
+        match args.as_slice() {
+            [Type::String(path)] => Ok(fs::read_to_string(path)
+                .map(Type::String)
+                .unwrap_or(Type::Nil)),
+            _ => Err(invalid_args("read_file", &args, &["string"])),
+        }
+    }
+
+    write_file(args: 2) [str, any] -> bool {
+        match args.as_slice() {
+            [Type::String(path), contents] => {
+                Ok(Type::Boolean(fs::write(path, format!("{:#}", contents)).is_ok()))
+            }
+            _ => Err(invalid_args("write_file", &args, &["string", "any"])),
+        }
+    }
+
+    append_file(args: 2) [str, any] -> bool {
+        match args.as_slice() {
+            [Type::String(path), contents] => {
+                Ok(Type::Boolean(append(path, &format!("{:#}", contents))))
+            }
+            _ => Err(invalid_args("append_file", &args, &["string", "any"])),
+        }
+    }
+
+    exists(args: 1) [str] -> bool {
+        match args.as_slice() {
+            [Type::String(path)] => Ok(Type::Boolean(Path::new(path).exists())),
+            _ => Err(invalid_args("exists", &args, &["string"])),
+        }
+    }
+
+    list_dir(args: 1) [str] -> any {
+        match args.as_slice() {
+            [Type::String(path)] => match fs::read_dir(path) {
+                Ok(entries) => {
+                    let mut names: Vec<_> = entries
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.file_name().to_string_lossy().into_owned())
+                        .collect();
+                    names.sort();
+                    Ok(Type::List(names.into_iter().map(Type::String).collect()))
+                }
+                Err(_) => Ok(Type::Nil),
+            },
+            _ => Err(invalid_args("list_dir", &args, &["string"])),
+        }
+    }
+}
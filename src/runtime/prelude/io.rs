@@ -0,0 +1,23 @@
+//! Printing, to the sink the `Env` was built with.
+
+use std::io::Write;
+
+prelude! {
+    with host;
+
+    print(args: 0..) [any] -> nil {
+        let mut out = host.output.borrow_mut();
+        for a in args {
+            write!(out, "{:#}", a).ok();
+        }
+
+        Ok(Type::Nil)
+    }
+
+    println(args: 0..) [any] -> nil {
+        print(host, args)?;
+        writeln!(host.output.borrow_mut()).ok();
+
+        Ok(Type::Nil)
+    }
+}
@@ -0,0 +1,25 @@
+//! JSON encoding and decoding, available with the `serde` feature. JSON
+//! objects have no kotoba counterpart yet, so documents containing them fail
+//! to parse. Failures yield `nil`.
+
+use super::invalid_args;
+
+prelude! {
+    json_parse(args: 1) [str] -> any {
+        match args.as_slice() {
+            [Type::String(json)] => Ok(serde_json::from_str(json).unwrap_or(Type::Nil)),
+            _ => Err(invalid_args("json_parse", &args, &["string"])),
+        }
+    }
+
+    json_stringify(args: 1..=2) [any, bool] -> any {
+        let json = match args.as_slice() {
+            [value] => serde_json::to_string(value),
+            [value, Type::Boolean(true)] => serde_json::to_string_pretty(value),
+            [value, Type::Boolean(false)] => serde_json::to_string(value),
+            _ => return Err(invalid_args("json_stringify", &args, &["any", "boolean"])),
+        };
+
+        Ok(json.map(Type::String).unwrap_or(Type::Nil))
+    }
+}
@@ -0,0 +1,147 @@
+use super::{invalid_args, invalid_value};
+use std::f64::consts;
+
+pub(super) fn constants() -> HashMap<String, Type> {
+    hashmap! {
+        "PI".to_owned() => Type::Number(consts::PI),
+        "E".to_owned() => Type::Number(consts::E)
+    }
+}
+
+/// Advances the generator of `host` (SplitMix64), returning the next 64
+/// random bits.
+fn next_random(host: &Host) -> u64 {
+    let s = host.rng.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+    host.rng.set(s);
+
+    let mut z = s;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Returns a uniformly distributed number in `[0, 1)`.
+fn next_float(host: &Host) -> f64 {
+    (next_random(host) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Applies `f` to the single numeric argument of the builtin `name`.
+fn unary(name: &str, args: &[Type], f: fn(f64) -> f64) -> Result<Type, RuntimeError> {
+    match args {
+        [Type::Number(n)] => Ok(Type::Number(f(*n))),
+        _ => Err(invalid_args(name, args, &["number"])),
+    }
+}
+
+/// Folds the numeric arguments of the builtin `name` with `f`.
+fn fold(name: &str, args: &[Type], f: fn(f64, f64) -> f64) -> Result<Type, RuntimeError> {
+    let numbers = args
+        .iter()
+        .map(|a| match a {
+            Type::Number(n) => Some(*n),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+
+    match numbers.as_deref() {
+        Some([first, rest @ ..]) => Ok(Type::Number(rest.iter().copied().fold(*first, f))),
+        _ => Err(invalid_args(name, args, &["number"])),
+    }
+}
+
+prelude! {
+    with host;
+
+    abs(args: 1) [num] -> num {
+        unary("abs", &args, f64::abs)
+    }
+
+    floor(args: 1) [num] -> num {
+        unary("floor", &args, f64::floor)
+    }
+
+    ceil(args: 1) [num] -> num {
+        unary("ceil", &args, f64::ceil)
+    }
+
+    round(args: 1) [num] -> num {
+        unary("round", &args, f64::round)
+    }
+
+    sqrt(args: 1) [num] -> num {
+        unary("sqrt", &args, f64::sqrt)
+    }
+
+    sin(args: 1) [num] -> num {
+        unary("sin", &args, f64::sin)
+    }
+
+    cos(args: 1) [num] -> num {
+        unary("cos", &args, f64::cos)
+    }
+
+    tan(args: 1) [num] -> num {
+        unary("tan", &args, f64::tan)
+    }
+
+    exp(args: 1) [num] -> num {
+        unary("exp", &args, f64::exp)
+    }
+
+    log(args: 1..=2) [num, num] -> num {
+        match args.as_slice() {
+            [Type::Number(n)] => Ok(Type::Number(n.ln())),
+            [Type::Number(n), Type::Number(base)] => Ok(Type::Number(n.log(*base))),
+            _ => Err(invalid_args("log", &args, &["number"])),
+        }
+    }
+
+    pow(args: 2) [num, num] -> num {
+        match args.as_slice() {
+            [Type::Number(base), Type::Number(exp)] => Ok(Type::Number(base.powf(*exp))),
+            _ => Err(invalid_args("pow", &args, &["number"])),
+        }
+    }
+
+    min(args: 1..) [num] -> num {
+        fold("min", &args, f64::min)
+    }
+
+    max(args: 1..) [num] -> num {
+        fold("max", &args, f64::max)
+    }
+
+    seed(args: 1) [num] -> nil {
+        match args.as_slice() {
+            [Type::Number(n)] => host.rng.set(n.to_bits()),
+            _ => return Err(invalid_args("seed", &args, &["number"])),
+        }
+
+        Ok(Type::Nil)
+    }
+
+    random(args: 0) [] -> num {
+        Ok(Type::Number(next_float(host)))
+    }
+
+    random_int(args: 2) [num, num] -> num {
+        match args.as_slice() {
+            [Type::Number(lo), Type::Number(hi)] if lo > hi => Err(invalid_value(
+                "random_int",
+                "the lower bound can't be greater than the upper one",
+            )),
+            [Type::Number(lo), Type::Number(hi)] => {
+                let (lo, hi) = (lo.ceil(), hi.floor());
+                let count = hi - lo + 1.0;
+                if lo > hi {
+                    Err(invalid_value("random_int", "there is no integer between the bounds"))
+                } else if !count.is_finite() {
+                    Err(invalid_value("random_int", "the bounds are too far apart"))
+                } else {
+                    Ok(Type::Number((lo + (next_float(host) * count).floor()).min(hi)))
+                }
+            }
+            _ => Err(invalid_args("random_int", &args, &["number"])),
+        }
+    }
+}
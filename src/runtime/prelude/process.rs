@@ -0,0 +1,26 @@
+use super::invalid_args;
+use std::{env, process};
+
+prelude! {
+    with host;
+
+    args(args: 0) [] -> list {
+        Ok(Type::List(host.args.iter().cloned().map(Type::String).collect()))
+    }
+
+    getenv(args: 1) [str] -> any {
+        match args.as_slice() {
+            [Type::String(name)] => Ok(env::var(name).map(Type::String).unwrap_or(Type::Nil)),
+            _ => Err(invalid_args("getenv", &args, &["string"])),
+        }
+    }
+
+    exit(args: 0..=1) [num] -> nil {
+        host.output.borrow_mut().flush().ok();
+        match args.as_slice() {
+            [] => process::exit(0),
+            [Type::Number(code)] => process::exit(*code as i32),
+            _ => Err(invalid_args("exit", &args, &["number"])),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Reading from the standard input, which yields `nil` at the end of input
+//! or if it can't be read. Prompts go to the sink the `Env` was built with.
+//!
+//! Lines are read by a thread of their own, so that a cancelled evaluation
+//! stops waiting for one. The line it was waiting for then goes to the next
+//! read.
+
+use super::{invalid_args, CANCEL_POLL};
+use std::{
+    io::{self, BufRead, Write},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Mutex, OnceLock, PoisonError,
+    },
+    thread,
+};
+
+/// The channels to the thread reading stdin, which reads a line for each
+/// request, and whether a line was requested but not received yet.
+struct StdinReader {
+    requests: Sender<()>,
+    lines: Receiver<Option<String>>,
+    pending: bool,
+}
+
+static STDIN: OnceLock<Mutex<StdinReader>> = OnceLock::new();
+
+impl StdinReader {
+    fn spawn() -> Mutex<StdinReader> {
+        let (requests, requested) = mpsc::channel();
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for () in requested {
+                if sender.send(read_stdin()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Mutex::new(StdinReader {
+            requests,
+            lines,
+            pending: false,
+        })
+    }
+}
+
+/// Reads a line from stdin, without the trailing line break.
+fn read_stdin() -> Option<String> {
+    let mut line = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => {
+            let len = line.trim_end_matches(&['\r', '\n'][..]).len();
+            line.truncate(len);
+            Some(line)
+        }
+    }
+}
+
+/// Waits for a line from stdin, unless the evaluation is cancelled.
+fn read_stdin_line(host: &Host) -> Result<Type, RuntimeError> {
+    let mut reader = STDIN
+        .get_or_init(StdinReader::spawn)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if !reader.pending {
+        reader.requests.send(()).ok();
+        reader.pending = true;
+    }
+
+    loop {
+        match reader.lines.recv_timeout(CANCEL_POLL) {
+            Ok(line) => {
+                reader.pending = false;
+                return Ok(line.map(Type::String).unwrap_or(Type::Nil));
+            }
+            Err(RecvTimeoutError::Timeout) => host.check_cancelled()?,
+            Err(RecvTimeoutError::Disconnected) => return Ok(Type::Nil),
+        }
+    }
+}
+
+prelude! {
+    with host;
+
+    input(args: 0..=1) [any] -> any {
+        match args.as_slice() {
+            [] => {}
+            [prompt] => {
+                let mut out = host.output.borrow_mut();
+                write!(out, "{:#}", prompt).ok();
+                out.flush().ok();
+            }
+            _ => return Err(invalid_args("input", &args, &["any"])),
+        }
+
+        read_stdin_line(host)
+    }
+
+    read_line(args: 0) [] -> any {
+        read_stdin_line(host)
+    }
+}
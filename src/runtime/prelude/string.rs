@@ -0,0 +1,161 @@
+use super::{invalid_args, invalid_value};
+
+/// Converts a (possibly fractional or negative) number into a character
+/// index, clamping it to zero.
+fn index(n: f64) -> usize {
+    n.max(0.0) as usize
+}
+
+/// Returns the error of the builtin `name` making a string longer than
+/// memory can be addressed.
+fn too_long(name: &str) -> RuntimeError {
+    invalid_value(name, "the result would be too long")
+}
+
+fn strings<'a>(items: impl Iterator<Item = &'a str>) -> Type {
+    Type::List(items.map(|s| Type::String(s.to_owned())).collect())
+}
+
+prelude! {
+    with host;
+
+    len(args: 1) [any] -> num {
+        match args.as_slice() {
+            [Type::String(s)] => Ok(Type::Number(s.chars().count() as f64)),
+            [Type::List(items)] => Ok(Type::Number(items.len() as f64)),
+            _ => Err(invalid_args("len", &args, &["string|list"])),
+        }
+    }
+
+    upper(args: 1) [str] -> str {
+        match args.as_slice() {
+            [Type::String(s)] => Ok(Type::String(s.to_uppercase())),
+            _ => Err(invalid_args("upper", &args, &["string"])),
+        }
+    }
+
+    lower(args: 1) [str] -> str {
+        match args.as_slice() {
+            [Type::String(s)] => Ok(Type::String(s.to_lowercase())),
+            _ => Err(invalid_args("lower", &args, &["string"])),
+        }
+    }
+
+    trim(args: 1) [str] -> str {
+        match args.as_slice() {
+            [Type::String(s)] => Ok(Type::String(s.trim().to_owned())),
+            _ => Err(invalid_args("trim", &args, &["string"])),
+        }
+    }
+
+    split(args: 1..=2) [str, str] -> list {
+        match args.as_slice() {
+            [Type::String(s)] => Ok(strings(s.split_whitespace())),
+            [Type::String(_), Type::String(sep)] if sep.is_empty() => {
+                Err(invalid_value("split", "the separator can't be empty"))
+            }
+            [Type::String(s), Type::String(sep)] => Ok(strings(s.split(sep.as_str()))),
+            _ => Err(invalid_args("split", &args, &["string", "string"])),
+        }
+    }
+
+    join(args: 2) [list, str] -> str {
+        match args.as_slice() {
+            [Type::List(items), Type::String(sep)] => {
+                let items: Vec<_> = items.iter().map(|i| format!("{:#}", i)).collect();
+                let len = items
+                    .iter()
+                    .try_fold(0usize, |len, i| len.checked_add(i.len()))
+                    .and_then(|len| {
+                        sep.len()
+                            .checked_mul(items.len().saturating_sub(1))?
+                            .checked_add(len)
+                    })
+                    .ok_or_else(|| too_long("join"))?;
+                host.check_string_len(len)?;
+
+                Ok(Type::String(items.join(sep)))
+            }
+            _ => Err(invalid_args("join", &args, &["list", "string"])),
+        }
+    }
+
+    replace(args: 3) [str, str, str] -> str {
+        match args.as_slice() {
+            [Type::String(s), Type::String(from), Type::String(to)] => {
+                let count = s.matches(from.as_str()).count();
+                let len = to
+                    .len()
+                    .checked_mul(count)
+                    .and_then(|added| (s.len() - from.len() * count).checked_add(added))
+                    .ok_or_else(|| too_long("replace"))?;
+                host.check_string_len(len)?;
+
+                Ok(Type::String(s.replace(from.as_str(), to)))
+            }
+            _ => Err(invalid_args("replace", &args, &["string", "string", "string"])),
+        }
+    }
+
+    find(args: 2) [str, str] -> any {
+        match args.as_slice() {
+            [Type::String(s), Type::String(needle)] => Ok(s
+                .find(needle.as_str())
+                .map(|i| Type::Number(s[..i].chars().count() as f64))
+                .unwrap_or(Type::Nil)),
+            _ => Err(invalid_args("find", &args, &["string", "string"])),
+        }
+    }
+
+    starts_with(args: 2) [str, str] -> bool {
+        match args.as_slice() {
+            [Type::String(s), Type::String(prefix)] => {
+                Ok(Type::Boolean(s.starts_with(prefix.as_str())))
+            }
+            _ => Err(invalid_args("starts_with", &args, &["string", "string"])),
+        }
+    }
+
+    ends_with(args: 2) [str, str] -> bool {
+        match args.as_slice() {
+            [Type::String(s), Type::String(suffix)] => {
+                Ok(Type::Boolean(s.ends_with(suffix.as_str())))
+            }
+            _ => Err(invalid_args("ends_with", &args, &["string", "string"])),
+        }
+    }
+
+    substring(args: 2..=3) [str, num, num] -> str {
+        let (s, start, end) = match args.as_slice() {
+            [Type::String(s), Type::Number(start)] => (s, index(*start), usize::MAX),
+            [Type::String(s), Type::Number(start), Type::Number(end)] => {
+                (s, index(*start), index(*end))
+            }
+            _ => return Err(invalid_args("substring", &args, &["string", "number"])),
+        };
+
+        Ok(Type::String(s.chars().skip(start).take(end.saturating_sub(start)).collect()))
+    }
+
+    repeat(args: 2) [str, num] -> str {
+        match args.as_slice() {
+            [Type::String(s), Type::Number(n)] => {
+                let n = index(*n);
+                let len = s.len().checked_mul(n).ok_or_else(|| too_long("repeat"))?;
+                host.check_string_len(len)?;
+
+                Ok(Type::String(s.repeat(n)))
+            }
+            _ => Err(invalid_args("repeat", &args, &["string", "number"])),
+        }
+    }
+
+    chars(args: 1) [str] -> list {
+        match args.as_slice() {
+            [Type::String(s)] => {
+                Ok(Type::List(s.chars().map(|c| Type::String(c.to_string())).collect()))
+            }
+            _ => Err(invalid_args("chars", &args, &["string"])),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use super::{invalid_args, invalid_value, CANCEL_POLL};
+use std::{
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+prelude! {
+    with host;
+
+    time(args: 0) [] -> num {
+        Ok(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| Type::Number(d.as_secs_f64()))
+            .unwrap_or(Type::Nil))
+    }
+
+    sleep(args: 1) [num] -> nil {
+        let deadline = match args.as_slice() {
+            [Type::Number(secs)] => Duration::try_from_secs_f64(*secs)
+                .ok()
+                .and_then(|duration| Instant::now().checked_add(duration))
+                .ok_or_else(|| invalid_value("sleep", "the duration is negative or too long"))?,
+            _ => return Err(invalid_args("sleep", &args, &["number"])),
+        };
+
+        // in short naps, to notice a cancellation
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            host.check_cancelled()?;
+            if left.is_zero() {
+                break;
+            }
+            thread::sleep(left.min(CANCEL_POLL));
+        }
+
+        Ok(Type::Nil)
+    }
+}
@@ -0,0 +1,133 @@
+//! Conversions between `Type` and Rust types through serde, available with
+//! the `serde` feature.
+//!
+//! Values map to the serde data model as follows: numbers without a
+//! fractional part that fit exactly in an `f64` are integers and other
+//! numbers are floats, lists are sequences, and `nil` is a unit or `None`.
+//! Host objects can't be serialized. Kotoba has no maps yet, so neither can
+//! maps or structs be converted to values.
+
+use super::Type;
+use serde::{
+    de::{self, DeserializeOwned, SeqAccess, Visitor},
+    ser::{self, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::fmt;
+
+/// The largest integer up to which every integer is exactly representable in
+/// an `f64`.
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+impl Serialize for Type {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Type::Number(n) if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER => {
+                serializer.serialize_i64(*n as i64)
+            }
+            Type::Number(n) => serializer.serialize_f64(*n),
+            Type::Boolean(b) => serializer.serialize_bool(*b),
+            Type::String(s) => serializer.serialize_str(s),
+            Type::List(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Type::Object(object) => Err(ser::Error::custom(format!(
+                "cannot serialize a host object of type {}",
+                object.class_name()
+            ))),
+            Type::Nil => serializer.serialize_unit(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Type {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(TypeVisitor)
+    }
+}
+
+struct TypeVisitor;
+
+impl<'de> Visitor<'de> for TypeVisitor {
+    type Value = Type;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number, boolean, string, list or nil")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Type, E> {
+        Ok(Type::Boolean(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Type, E> {
+        Ok(Type::Number(v as f64))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Type, E> {
+        Ok(Type::Number(v as f64))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Type, E> {
+        Ok(Type::Number(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Type, E> {
+        Ok(Type::String(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Type, E> {
+        Ok(Type::String(v))
+    }
+
+    fn visit_char<E: de::Error>(self, v: char) -> Result<Type, E> {
+        Ok(Type::String(v.to_string()))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Type, E> {
+        Ok(Type::Nil)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Type, E> {
+        Ok(Type::Nil)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Type, D::Error> {
+        Type::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Type, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Type::List(items))
+    }
+}
+
+/// Converts any serializable Rust value to a `Type`.
+///
+/// ```
+/// use kotoba::runtime::{to_value, Type};
+///
+/// let value = to_value(&(1, vec!["a"], None::<bool>)).unwrap();
+/// assert_eq!(value.to_string(), "[1, [\"a\"], nil]");
+/// ```
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Type, serde_json::Error> {
+    Type::deserialize(serde_json::to_value(value)?)
+}
+
+/// Converts a `Type` to any deserializable Rust value.
+///
+/// ```
+/// use kotoba::runtime::{from_value, Type};
+///
+/// let list = Type::List(vec![Type::Number(1.0), Type::Nil]);
+/// assert_eq!(from_value::<Vec<Option<u8>>>(&list).unwrap(), vec![Some(1), None]);
+/// ```
+pub fn from_value<T: DeserializeOwned>(value: &Type) -> Result<T, serde_json::Error> {
+    T::deserialize(serde_json::to_value(value)?)
+}
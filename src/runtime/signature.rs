@@ -0,0 +1,47 @@
+use super::Arity;
+use crate::parser::{Param, TypeName};
+
+/// The types of the parameters of a function, and of its result, as the
+/// `checker` sees them. Arguments that may be of several types, and results
+/// that may be `nil`, are `any`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    /// The parameters taking positional arguments. Builtins and native
+    /// functions take no named arguments, so theirs have no names.
+    pub params: Vec<(String, TypeName)>,
+    /// The type of each argument collected by a rest parameter.
+    pub rest: Option<TypeName>,
+    pub returns: TypeName,
+}
+
+impl Signature {
+    /// Returns the signature of a function declared with `params` and the
+    /// result type `returns`, taking unannotated types as `any`.
+    pub fn of_params(params: &[Param], returns: Option<TypeName>) -> Self {
+        let annotation = |p: &Param| p.annotation.unwrap_or(TypeName::Any);
+        Signature {
+            params: params
+                .iter()
+                .filter(|p| !p.rest)
+                .map(|p| (p.name.clone(), annotation(p)))
+                .collect(),
+            rest: params.iter().find(|p| p.rest).map(annotation),
+            returns: returns.unwrap_or(TypeName::Any),
+        }
+    }
+
+    /// Returns the signature of a function without parameter names taking
+    /// `arity` arguments of the types `params`. If it is variadic, the last
+    /// of `params` is the type of the arguments past the others.
+    pub(crate) fn unnamed(arity: Arity, params: &[TypeName], returns: TypeName) -> Self {
+        let (params, rest) = match (arity.max, params.split_last()) {
+            (None, Some((&rest, params))) => (params, Some(rest)),
+            _ => (params, None),
+        };
+        Signature {
+            params: params.iter().map(|&t| (String::new(), t)).collect(),
+            rest,
+            returns,
+        }
+    }
+}
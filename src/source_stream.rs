@@ -1,6 +1,6 @@
 use std::fmt;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Position {
     pub line: usize,
     pub character: usize,
@@ -23,11 +23,23 @@ impl<'s> SourceStream<'s> {
     /// Initializes a new `SourceStream` with the given source code `&str`.
     /// `source` must be a valid ASCII string.
     pub fn new(source: &'s str) -> Self {
+        Self::with_position(
+            source,
+            Position {
+                line: 1,
+                character: 1,
+            },
+        )
+    }
+
+    /// Initializes a new `SourceStream` over a fragment of a larger source,
+    /// with `position` being the position of its first byte.
+    pub fn with_position(source: &'s str, position: Position) -> Self {
         Self {
             source: source.as_bytes(),
             index: 0,
-            cur_line: 1,
-            cur_char: 1,
+            cur_line: position.line,
+            cur_char: position.character,
         }
     }
 
@@ -37,6 +49,11 @@ impl<'s> SourceStream<'s> {
         self.source.get(self.index).cloned()
     }
 
+    /// Returns `true` if the bytes ahead in the stream are `prefix`.
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        self.source[self.index..].starts_with(prefix.as_bytes())
+    }
+
     /// Returns the next byte in the stream, consuming it,
     /// or `None` if the stream is empty.
     pub fn next(&mut self) -> Option<u8> {
@@ -52,6 +69,18 @@ impl<'s> SourceStream<'s> {
         })
     }
 
+    /// Returns the next character in the stream, which may span several
+    /// bytes, consuming it, or `None` if the stream is empty.
+    pub fn next_char(&mut self) -> Option<char> {
+        let rest = &self.source[self.index..];
+        let len = (1..=rest.len().min(4)).find(|&n| std::str::from_utf8(&rest[..n]).is_ok())?;
+        let c = std::str::from_utf8(&rest[..len]).ok()?.chars().next();
+        for _ in 0..len {
+            self.next();
+        }
+        c
+    }
+
     /// If the next byte in the stream is equal to `expected`,
     /// consumes it and return `true`, otherwise returns `false`.
     pub fn expect(&mut self, expected: u8) -> bool {
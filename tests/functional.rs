@@ -1,122 +1,2077 @@
-// #![cfg(test)]
-
-// use kotoba::{eval::*, parser::*};
-
-// fn eval_eq(source: &str, expected: Type) {
-//     assert_eq!(
-//         // Env::default().eval(&Parser::new(source).parse()),
-//         expected
-//     );
-// }
-
-// #[test]
-// fn literals() {
-//     eval_eq("1", Type::Number(1.0));
-//     eval_eq("123.123", Type::Number(123.123));
-//     eval_eq("true", Type::Boolean(true));
-//     eval_eq("false", Type::Boolean(false));
-//     eval_eq("\"\"", Type::String("".to_string()));
-//     eval_eq("\"hello world\"", Type::String("hello world".to_string()));
-//     eval_eq("nil", Type::Nil);
-// }
-
-// #[test]
-// fn groupings() {
-//     eval_eq("(1)", Type::Number(1.0));
-//     eval_eq("(123.123)", Type::Number(123.123));
-//     eval_eq("(true)", Type::Boolean(true));
-//     eval_eq("(false)", Type::Boolean(false));
-//     eval_eq("(\"\")", Type::String("".to_string()));
-//     eval_eq("(\"hello world\")", Type::String("hello world".to_string()));
-//     eval_eq("(nil)", Type::Nil);
-
-//     eval_eq("((((1))))", Type::Number(1.0));
-// }
-
-// #[test]
-// fn unary_minus() {
-//     eval_eq("-1", Type::Number(-1.0));
-//     eval_eq("-123.123", Type::Number(-123.123));
-
-//     eval_eq("-(1)", Type::Number(-1.0));
-//     eval_eq("-(123.123)", Type::Number(-123.123));
-
-//     eval_eq("--1", Type::Number(1.0));
-//     eval_eq("-----------123.123", Type::Number(-123.123));
-// }
-
-// #[test]
-// fn unary_bang() {
-//     eval_eq("!true", Type::Boolean(false));
-//     eval_eq("!false", Type::Boolean(true));
-
-//     eval_eq("!(true)", Type::Boolean(false));
-//     eval_eq("!(false)", Type::Boolean(true));
-
-//     eval_eq("!!!!!!!!!true", Type::Boolean(false));
-//     eval_eq("!!!!!!!!false", Type::Boolean(false));
-// }
-
-// #[test]
-// fn mult_expr() {
-//     eval_eq("2 * 3", Type::Number(6.0));
-//     eval_eq("-2.5 * 4", Type::Number(-10.0));
-//     eval_eq("2 * 3 * 4", Type::Number(24.0));
-//     eval_eq(
-//         "2.1 * 3.2 * 4.3 * 5.4 * 6.5",
-//         Type::Number(2.1 * 3.2 * 4.3 * 5.4 * 6.5),
-//     );
-// }
-
-// #[test]
-// fn div_expr() {
-//     eval_eq("18 / 3", Type::Number(6.0));
-//     eval_eq("-100 / 2.5", Type::Number(-40.0));
-//     eval_eq("2 / 3 / 4", Type::Number(2.0 / 3.0 / 4.0));
-//     eval_eq(
-//         "2.1 / 3.2 / 4.3 / 5.4 / 6.5",
-//         Type::Number(2.1 / 3.2 / 4.3 / 5.4 / 6.5),
-//     );
-// }
-
-// #[test]
-// fn mixed_mult_and_div_expr() {
-//     eval_eq("18 / 3 * 4.5", Type::Number(27.0));
-//     eval_eq("-100 * 4 / 2.5", Type::Number(-160.0));
-
-//     eval_eq("18 / (3 * 4.5)", Type::Number(18.0 / (3.0 * 4.5)));
-//     eval_eq("-100 * (4 / 2.5)", Type::Number(-100.0 * (4.0 / 2.5)));
-// }
-
-// #[test]
-// fn add_expr() {
-//     eval_eq("18 + 3", Type::Number(21.0));
-//     eval_eq("-100 + 2.5", Type::Number(-97.5));
-//     eval_eq("100 + -2.5", Type::Number(97.5));
-//     eval_eq("2 + 3 + 4", Type::Number(9.0));
-//     eval_eq(
-//         "2.1 + 3.2 + 4.3 + 5.4 + 6.5",
-//         Type::Number(2.1 + 3.2 + 4.3 + 5.4 + 6.5),
-//     );
-// }
-
-// #[test]
-// fn sub_expr() {
-//     eval_eq("18 - 3", Type::Number(15.0));
-//     eval_eq("-100 - 2.5", Type::Number(-102.5));
-//     eval_eq("100 - -2.5", Type::Number(102.5));
-//     eval_eq("2 - 3 - 4", Type::Number(-5.0));
-//     eval_eq(
-//         "2.1 - 3.2 - 4.3 - 5.4 - 6.5",
-//         Type::Number(2.1 - 3.2 - 4.3 - 5.4 - 6.5),
-//     );
-// }
-
-// #[test]
-// fn mixed_add_and_sub_expr() {
-//     eval_eq("18 + 3 - 4.5", Type::Number(16.5));
-//     eval_eq("-100 - 4 + 2.5", Type::Number(-101.5));
-
-//     eval_eq("18 + (3 - 4.5)", Type::Number(16.5));
-//     eval_eq("-100 - (4 + 2.5)", Type::Number(-106.5));
-// }
+#![cfg(test)]
+
+use kotoba::{parser::*, runtime::*};
+
+fn eval_eq(source: &str, expected: Type) {
+    assert_eq!(
+        Env::eval(Env::new(), &Parser::new(source).parse()).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn literals() {
+    eval_eq("1", Type::Number(1.0));
+    eval_eq("123.123", Type::Number(123.123));
+    eval_eq("true", Type::Boolean(true));
+    eval_eq("false", Type::Boolean(false));
+    eval_eq("\"\"", Type::String("".to_string()));
+    eval_eq("\"hello world\"", Type::String("hello world".to_string()));
+    eval_eq("nil", Type::Nil);
+}
+
+#[test]
+fn groupings() {
+    eval_eq("(1)", Type::Number(1.0));
+    eval_eq("(123.123)", Type::Number(123.123));
+    eval_eq("(true)", Type::Boolean(true));
+    eval_eq("(false)", Type::Boolean(false));
+    eval_eq("(\"\")", Type::String("".to_string()));
+    eval_eq("(\"hello world\")", Type::String("hello world".to_string()));
+    eval_eq("(nil)", Type::Nil);
+
+    eval_eq("((((1))))", Type::Number(1.0));
+}
+
+#[test]
+fn unary_minus() {
+    eval_eq("-1", Type::Number(-1.0));
+    eval_eq("-123.123", Type::Number(-123.123));
+
+    eval_eq("-(1)", Type::Number(-1.0));
+    eval_eq("-(123.123)", Type::Number(-123.123));
+
+    eval_eq("--1", Type::Number(1.0));
+    eval_eq("-----------123.123", Type::Number(-123.123));
+}
+
+#[test]
+fn unary_bang() {
+    eval_eq("!true", Type::Boolean(false));
+    eval_eq("!false", Type::Boolean(true));
+
+    eval_eq("!(true)", Type::Boolean(false));
+    eval_eq("!(false)", Type::Boolean(true));
+
+    eval_eq("!!!!!!!!!true", Type::Boolean(false));
+    eval_eq("!!!!!!!!false", Type::Boolean(false));
+}
+
+#[test]
+fn mult_expr() {
+    eval_eq("2 * 3", Type::Number(6.0));
+    eval_eq("-2.5 * 4", Type::Number(-10.0));
+    eval_eq("2 * 3 * 4", Type::Number(24.0));
+    eval_eq(
+        "2.1 * 3.2 * 4.3 * 5.4 * 6.5",
+        Type::Number(2.1 * 3.2 * 4.3 * 5.4 * 6.5),
+    );
+}
+
+#[test]
+fn div_expr() {
+    eval_eq("18 / 3", Type::Number(6.0));
+    eval_eq("-100 / 2.5", Type::Number(-40.0));
+    eval_eq("2 / 3 / 4", Type::Number(2.0 / 3.0 / 4.0));
+    eval_eq(
+        "2.1 / 3.2 / 4.3 / 5.4 / 6.5",
+        Type::Number(2.1 / 3.2 / 4.3 / 5.4 / 6.5),
+    );
+}
+
+#[test]
+fn mixed_mult_and_div_expr() {
+    eval_eq("18 / 3 * 4.5", Type::Number(27.0));
+    eval_eq("-100 * 4 / 2.5", Type::Number(-160.0));
+
+    eval_eq("18 / (3 * 4.5)", Type::Number(18.0 / (3.0 * 4.5)));
+    eval_eq("-100 * (4 / 2.5)", Type::Number(-100.0 * (4.0 / 2.5)));
+}
+
+#[test]
+fn add_expr() {
+    eval_eq("18 + 3", Type::Number(21.0));
+    eval_eq("-100 + 2.5", Type::Number(-97.5));
+    eval_eq("100 + -2.5", Type::Number(97.5));
+    eval_eq("2 + 3 + 4", Type::Number(9.0));
+    eval_eq(
+        "2.1 + 3.2 + 4.3 + 5.4 + 6.5",
+        Type::Number(2.1 + 3.2 + 4.3 + 5.4 + 6.5),
+    );
+}
+
+#[test]
+fn sub_expr() {
+    eval_eq("18 - 3", Type::Number(15.0));
+    eval_eq("-100 - 2.5", Type::Number(-102.5));
+    eval_eq("100 - -2.5", Type::Number(102.5));
+    eval_eq("2 - 3 - 4", Type::Number(-5.0));
+    eval_eq(
+        "2.1 - 3.2 - 4.3 - 5.4 - 6.5",
+        Type::Number(2.1 - 3.2 - 4.3 - 5.4 - 6.5),
+    );
+}
+
+#[test]
+fn mixed_add_and_sub_expr() {
+    eval_eq("18 + 3 - 4.5", Type::Number(16.5));
+    eval_eq("-100 - 4 + 2.5", Type::Number(-101.5));
+
+    eval_eq("18 + (3 - 4.5)", Type::Number(16.5));
+    eval_eq("-100 - (4 + 2.5)", Type::Number(-106.5));
+}
+
+#[test]
+fn match_literals() {
+    eval_eq(
+        "match 2: 1 => \"one\", 2 => \"two\";",
+        Type::String("two".to_string()),
+    );
+    eval_eq("match -1: 1 => 1, -1 => -1;", Type::Number(-1.0));
+    eval_eq(
+        "match \"a\": \"a\" => true, _ => false;",
+        Type::Boolean(true),
+    );
+    eval_eq("match nil: nil => 0, _ => 1;", Type::Number(0.0));
+    eval_eq("match 3: 1 => 1, 2 => 2;", Type::Nil);
+}
+
+#[test]
+fn match_wildcard_and_binding() {
+    eval_eq("match 5: 1 => 1, _ => 0;", Type::Number(0.0));
+    eval_eq("match 5: 1 => 1, n => n * 2;", Type::Number(10.0));
+}
+
+#[test]
+fn match_guards() {
+    eval_eq(
+        "match 5: n if n > 10 => \"big\", n if n > 0 => \"small\", _ => \"neg\";",
+        Type::String("small".to_string()),
+    );
+    eval_eq("match 5: 5 if false => 1, _ => 2;", Type::Number(2.0));
+}
+
+#[test]
+fn match_exhaustiveness_warnings() {
+    fn warnings(source: &str) -> usize {
+        let mut parser = Parser::new(source);
+        parser.parse();
+        parser.warnings().len()
+    }
+
+    assert_eq!(warnings("match true: true => 1;"), 1);
+    assert_eq!(warnings("match true: true => 1, false => 0;"), 0);
+    assert_eq!(warnings("match true: true if x => 1, false => 0;"), 1);
+    assert_eq!(warnings("match nil: nil => 1, true => 0, false => 2;"), 0);
+    assert_eq!(warnings("match nil: nil => 1, true => 0;"), 1);
+    assert_eq!(warnings("match true: true => 1, _ => 0;"), 0);
+    assert_eq!(warnings("match 1: 1 => 1;"), 0);
+}
+
+#[test]
+fn if_expressions() {
+    eval_eq("if true: 1 else 2;", Type::Number(1.0));
+    eval_eq("if false: 1 else 2;", Type::Number(2.0));
+    eval_eq("if false: 1;", Type::Nil);
+    eval_eq("y = if 1 > 2: 1 else 2; y", Type::Number(2.0));
+    eval_eq("y = 1 + if true: 1 else 2;, y", Type::Number(2.0));
+    eval_eq(
+        "x = 3, (if x > 2: \"big\" else \"small\";)",
+        Type::String("big".to_string()),
+    );
+}
+
+#[test]
+fn block_values() {
+    eval_eq("if true: x = 1, x + 1;", Type::Number(2.0));
+    eval_eq("if true: if true: 1, 2;;", Type::Number(2.0));
+    eval_eq("if true: while false: 1;;", Type::Nil);
+    eval_eq(
+        "y = match 2: 2 => \"two\", _ => \"other\"; y",
+        Type::String("two".to_string()),
+    );
+}
+
+#[test]
+fn string_interpolation() {
+    eval_eq(
+        "x = 1, \"value is {x + 1}\"",
+        Type::String("value is 2".to_string()),
+    );
+    eval_eq("s = \"a\", \"{s}{s}\"", Type::String("aa".to_string()));
+    eval_eq(
+        "\"{true} {nil} {1.5}\"",
+        Type::String("true nil 1.5".to_string()),
+    );
+    eval_eq(
+        "\"{\"nested {1 + 1}\"}!\"",
+        Type::String("nested 2!".to_string()),
+    );
+    eval_eq("\"{{x}\"", Type::String("{x}".to_string()));
+    eval_eq(
+        "\"{if true: \"y\" else \"n\";}\"",
+        Type::String("y".to_string()),
+    );
+}
+
+fn string(s: &str) -> Type {
+    Type::String(s.to_string())
+}
+
+fn strings(items: &[&str]) -> Type {
+    Type::List(items.iter().map(|s| string(s)).collect())
+}
+
+#[test]
+fn lists() {
+    eval_eq("[]", Type::List(vec![]));
+    eval_eq(
+        "x = 2, [1, x, \"a\"]",
+        Type::List(vec![Type::Number(1.0), Type::Number(2.0), string("a")]),
+    );
+    eval_eq("match [1, 2]: [a, b] => a + b;", Type::Number(3.0));
+    eval_eq(
+        "match [1, [2]]: [1, [_, _]] => 1, [_, [b]] => b;",
+        Type::Number(2.0),
+    );
+    eval_eq("match []: [_] => 1, [] => 0;", Type::Number(0.0));
+}
+
+#[test]
+fn string_builtins() {
+    eval_eq("len(\"héllo\")", Type::Number(5.0));
+    eval_eq("len([1, 2])", Type::Number(2.0));
+    eval_eq("upper(\"abc\")", string("ABC"));
+    eval_eq("lower(\"ABC\")", string("abc"));
+    eval_eq("trim(\"  a b \")", string("a b"));
+    eval_eq("split(\" a  b \")", strings(&["a", "b"]));
+    eval_eq("split(\"a,b,,c\", \",\")", strings(&["a", "b", "", "c"]));
+    eval_eq("join([\"a\", 1, true], \"-\")", string("a-1-true"));
+    eval_eq("join(split(\"a b\"), \"\")", string("ab"));
+    eval_eq("replace(\"aXbX\", \"X\", \"y\")", string("ayby"));
+    eval_eq("find(\"héllo\", \"l\")", Type::Number(2.0));
+    eval_eq("find(\"hello\", \"z\")", Type::Nil);
+    eval_eq("starts_with(\"hello\", \"he\")", Type::Boolean(true));
+    eval_eq("ends_with(\"hello\", \"he\")", Type::Boolean(false));
+    eval_eq("substring(\"héllo\", 1, 3)", string("él"));
+    eval_eq("substring(\"héllo\", 3)", string("lo"));
+    eval_eq("substring(\"abc\", 2, 100)", string("c"));
+    eval_eq("substring(\"abc\", 2, 1)", string(""));
+    eval_eq("repeat(\"ab\", 3)", string("ababab"));
+    eval_eq("chars(\"hé\")", strings(&["h", "é"]));
+}
+
+#[test]
+fn string_comparison() {
+    eval_eq("\"a\" < \"b\"", Type::Boolean(true));
+    eval_eq("\"b\" <= \"a\"", Type::Boolean(false));
+    eval_eq("\"abc\" > \"abb\"", Type::Boolean(true));
+    eval_eq("\"a\" >= \"a\"", Type::Boolean(true));
+}
+
+#[test]
+fn power_and_integer_division() {
+    eval_eq("2 ** 10", Type::Number(1024.0));
+    eval_eq("2 ** 3 ** 2", Type::Number(512.0));
+    eval_eq("-2 ** 2", Type::Number(-4.0));
+    eval_eq("2 * 3 ** 2", Type::Number(18.0));
+    eval_eq("7 ~/ 2", Type::Number(3.0));
+    eval_eq("-7 ~/ 2", Type::Number(-4.0));
+    eval_eq("7.5 ~/ 2.5 * 2", Type::Number(6.0));
+}
+
+#[test]
+fn math_builtins() {
+    eval_eq("abs(-2)", Type::Number(2.0));
+    eval_eq("floor(1.7)", Type::Number(1.0));
+    eval_eq("ceil(1.2)", Type::Number(2.0));
+    eval_eq("round(2.5)", Type::Number(3.0));
+    eval_eq("sqrt(16)", Type::Number(4.0));
+    eval_eq("pow(2, 0.5)", Type::Number(2f64.powf(0.5)));
+    eval_eq("min(3, 1, 2)", Type::Number(1.0));
+    eval_eq("max(3, 1, 2)", Type::Number(3.0));
+    eval_eq("sin(0) + cos(0) + tan(0)", Type::Number(1.0));
+    eval_eq("log(E)", Type::Number(1.0));
+    eval_eq("log(8, 2)", Type::Number(3.0));
+    eval_eq("exp(0)", Type::Number(1.0));
+    eval_eq("PI", Type::Number(std::f64::consts::PI));
+}
+
+#[test]
+fn seeded_random() {
+    let rolls = "seed(42), [random(), random_int(1, 6), random_int(1, 6)]";
+    let first = Env::eval(Env::new(), &Parser::new(rolls).parse()).unwrap();
+    let second = Env::eval(Env::new(), &Parser::new(rolls).parse()).unwrap();
+    assert_eq!(first, second);
+
+    eval_eq(
+        "seed(1), x = random(), x >= 0 and x < 1",
+        Type::Boolean(true),
+    );
+    eval_eq("random_int(3, 3)", Type::Number(3.0));
+    eval_eq("random_int(1.5, 2.5)", Type::Number(2.0));
+
+    use std::{cell::RefCell, rc::Rc};
+
+    // each `Env` has a generator of its own
+    let (a, b) = (Env::new(), Env::new());
+    let eval = |env: &Rc<RefCell<Env>>, source: &str| {
+        Env::eval(env.clone(), &Parser::new(source).parse()).unwrap()
+    };
+    eval(&a, "seed(7)");
+    eval(&b, "seed(7)");
+    assert_eq!(eval(&a, "random()"), eval(&b, "random()"));
+}
+
+#[test]
+fn builtin_argument_errors() {
+    let error = |source: &str| Env::eval(Env::new(), &Parser::new(source).parse()).unwrap_err();
+
+    assert_eq!(
+        error("x = 1, upper(x)"),
+        RuntimeError::ArgumentType {
+            function: "upper".to_owned(),
+            position: 1,
+            expected: "a string".to_owned(),
+            found: "number".to_owned(),
+        }
+    );
+    assert_eq!(
+        error("len(nil)").to_string(),
+        "argument 1 of `len` must be a string or a list, found nil"
+    );
+    assert_eq!(
+        error("max(1, 2, \"3\")").to_string(),
+        "argument 3 of `max` must be a number, found string"
+    );
+    assert_eq!(
+        error("substring(\"abc\", true)").to_string(),
+        "argument 2 of `substring` must be a number, found boolean"
+    );
+    assert_eq!(
+        error("split(\"abc\", \"\")").to_string(),
+        "split: the separator can't be empty"
+    );
+    assert_eq!(
+        error("random_int(2, 1)").to_string(),
+        "random_int: the lower bound can't be greater than the upper one"
+    );
+    assert_eq!(
+        error("random_int(1.5, 1.7)").to_string(),
+        "random_int: there is no integer between the bounds"
+    );
+    assert_eq!(
+        error("random_int(-(10 ** 308), 10 ** 308)").to_string(),
+        "random_int: the bounds are too far apart"
+    );
+}
+
+#[test]
+fn user_defined_functions() {
+    eval_eq("fn add(a, b): ret a + b; add(1, 2)", Type::Number(3.0));
+    eval_eq("fn last(a): a * 2; last(4)", Type::Number(8.0));
+    eval_eq(
+        "fn sign(n): if n < 0: ret -1; ret 1; sign(-5)",
+        Type::Number(-1.0),
+    );
+    eval_eq(
+        "fn fact(n): if n <= 1: 1 else n * fact(n - 1);; fact(5)",
+        Type::Number(120.0),
+    );
+    eval_eq("x = 10, fn get(): x; get()", Type::Number(10.0));
+    eval_eq("ret 1, 2", Type::Number(1.0));
+}
+
+#[test]
+fn arity() {
+    let error = |source: &str| {
+        Env::eval(Env::new(), &Parser::new(source).parse())
+            .unwrap_err()
+            .to_string()
+    };
+
+    assert_eq!(
+        error("fn f(a, b): b; f(1)"),
+        "`f` takes 2 arguments but 1 was given"
+    );
+    assert_eq!(
+        error("fn f(): 1; f(1)"),
+        "`f` takes 0 arguments but 1 was given"
+    );
+    assert_eq!(
+        error("sqrt(1, 2)"),
+        "`sqrt` takes 1 argument but 2 were given"
+    );
+    assert_eq!(
+        error("split()"),
+        "`split` takes 1 to 2 arguments but 0 were given"
+    );
+    assert_eq!(
+        error("max()"),
+        "`max` takes at least 1 argument but 0 were given"
+    );
+    eval_eq("split(\"a b\")", Type::List(vec![string("a"), string("b")]));
+    eval_eq("max(1, 5, 3)", Type::Number(5.0));
+    eval_eq("print()", Type::Nil);
+
+    assert!(Arity::between(1, 2).accepts(2));
+    assert!(!Arity::between(1, 2).accepts(3));
+    assert!(Arity::at_least(0).accepts(100));
+}
+
+#[test]
+fn parameters() {
+    let greet = "fn greet(name, greeting = \"hello\", punct = \"!\"): \
+                 ret \"{greeting}, {name}{punct}\";\n";
+    let call = |call: &str| format!("{}{}", greet, call);
+    eval_eq(&call("greet(\"ann\")"), string("hello, ann!"));
+    eval_eq(&call("greet(\"bob\", \"hi\")"), string("hi, bob!"));
+    eval_eq(
+        &call("greet(punct: \"?\", name: \"cy\")"),
+        string("hello, cy?"),
+    );
+    eval_eq(&call("greet(\"di\", punct: \".\")"), string("hello, di."));
+
+    eval_eq(
+        "fn scale(x, by = x): ret x * by; scale(3)",
+        Type::Number(9.0),
+    );
+    eval_eq(
+        "fn f(first, *rest): ret [first, rest]; f(1, 2, 3)",
+        Type::List(vec![
+            Type::Number(1.0),
+            Type::List(vec![Type::Number(2.0), Type::Number(3.0)]),
+        ]),
+    );
+    eval_eq("fn f(*all): all; f()", Type::List(vec![]));
+
+    let error = |source: &str| {
+        Env::eval(Env::new(), &Parser::new(source).parse())
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(
+        error(&call("greet(greeting: \"hi\")")),
+        "`greet` is missing an argument for `name`"
+    );
+    assert_eq!(
+        error(&call("greet(\"ann\", name: \"bob\")")),
+        "`greet` got more than one argument for `name`"
+    );
+    assert_eq!(
+        error(&call("greet(\"ann\", nmae: \"bob\")")),
+        "`greet` has no parameter named `nmae`"
+    );
+    assert_eq!(
+        error("fn f(*rest): rest; f(rest: 1)"),
+        "`f` has no parameter named `rest`"
+    );
+    assert_eq!(
+        error(&call("greet(1, 2, 3, 4)")),
+        "`greet` takes 1 to 3 arguments but 4 were given"
+    );
+    assert_eq!(error("sqrt(n: 4)"), "`sqrt` has no parameter named `n`");
+
+    let syntax_error = |source: &str| Parser::new(source).try_parse().unwrap_err().to_string();
+    assert_eq!(
+        syntax_error("fn f(*rest, b): 1;"),
+        "syntax error at 1:13: parameter `b` after the rest parameter"
+    );
+    assert_eq!(
+        syntax_error("fn f(a = 1, b): 1;"),
+        "syntax error at 1:13: parameter `b` needs a default, as one before it has"
+    );
+    assert_eq!(
+        syntax_error("f(a: 1, 2)"),
+        "syntax error at 1:1: positional argument after a named one in the call to `f`"
+    );
+    assert_eq!(
+        syntax_error("fn f(a,): 1;"),
+        "syntax error at 1:7: missing identifier after `,`"
+    );
+
+    let source = "fn f(a, b = [1], *c): a; f(0, b: 2)";
+    let ast = Parser::new(source).parse();
+    assert_eq!(
+        ast.to_sexpr(),
+        "(program (fn f (a (= b (list 1)) *c) (block a)) (call f 0 (named b 2)))"
+    );
+    let analysis = kotoba::analysis::Analysis::new(source, &ast);
+    assert_eq!(analysis.symbols[0].detail, "fn f(a, b = [1], *c)");
+    assert_eq!(
+        kotoba::formatter::format(source).unwrap(),
+        "fn f(a, b = [1], *c): a;\nf(0, b: 2)\n"
+    );
+}
+
+#[test]
+fn type_annotations() {
+    use kotoba::checker;
+
+    let source = "fn f(a: num, b: str = \"x\", *c: bool) -> list: ret [a, b, c];\n\
+                  x: num = 1,\n\
+                  f(x, \"y\", true)";
+    eval_eq(
+        source,
+        Type::List(vec![
+            Type::Number(1.0),
+            string("y"),
+            Type::List(vec![Type::Boolean(true)]),
+        ]),
+    );
+    let ast = Parser::new(source).parse();
+    assert!(checker::check(&ast).is_empty());
+    assert_eq!(
+        ast.to_sexpr(),
+        concat!(
+            "(program\n",
+            "  (fn f (a:num (= b:str \"x\") *c:bool) (-> list) (block (ret (list a b c))))\n",
+            "  (= x:num 1)\n",
+            "  (call f x \"y\" true))"
+        )
+    );
+    let analysis = kotoba::analysis::Analysis::new(source, &ast);
+    assert_eq!(
+        analysis.symbols[0].detail,
+        "fn f(a: num, b: str = \"x\", *c: bool) -> list"
+    );
+    assert_eq!(
+        analysis
+            .symbols
+            .iter()
+            .map(|s| &s.name[..])
+            .collect::<Vec<_>>(),
+        ["f", "a", "b", "c", "x"]
+    );
+    assert_eq!(
+        kotoba::formatter::format(source).unwrap(),
+        concat!(
+            "fn f(a: num, b: str = \"x\", *c: bool) -> list: ret [a, b, c];\n",
+            "x: num = 1,\n",
+            "f(x, \"y\", true)\n"
+        )
+    );
+
+    let errors = |source: &str| {
+        checker::check(&Parser::new(source).parse())
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        errors("\"a\" - 1"),
+        ["type error at 1:1: `-` can't be applied to str and num"]
+    );
+    assert_eq!(errors("x = \"a\", -x + len(x)"), Vec::<String>::new());
+    assert_eq!(
+        errors("x: num = 1, x = \"a\""),
+        ["type error at 1:17: expected num for `x`, found str"]
+    );
+    assert_eq!(
+        errors("fn f(a: str): ret upper(a); f(1), f(a: true), sqrt(f(\"a\"))"),
+        [
+            "type error at 1:31: expected str for parameter `a` of `f`, found num",
+            "type error at 1:40: expected str for parameter `a` of `f`, found bool",
+        ]
+    );
+    assert_eq!(
+        errors("fn f() -> num: \"a\"; fn g(n) -> str: if n: ret 1; \"b\";"),
+        [
+            "type error at 1:16: expected num for the result of `f`, found str",
+            "type error at 1:47: expected str for the result of `g`, found num",
+        ]
+    );
+    // the end of a body with a `ret` in it may never be reached
+    for source in [
+        "fn f() -> num: while true: ret 1;; f()",
+        "fn f(x) -> num: if x: ret 1; \"a\";",
+        "fn f() -> num: while true: nil;",
+    ] {
+        assert_eq!(errors(source), Vec::<String>::new(), "{}", source);
+    }
+    assert_eq!(
+        errors("fn f() -> num: fn g(): ret 1; \"a\";"),
+        ["type error at 1:31: expected num for the result of `f`, found str"]
+    );
+    assert_eq!(
+        errors("if 1: 2; while !\"x\" and true: 1; max(1, \"2\") < \"3\""),
+        [
+            "type error at 1:4: expected bool for the condition, found num",
+            "type error at 1:16: `!` can't be applied to str",
+            "type error at 1:34: `<` can't be applied to num and str",
+            "type error at 1:41: expected num for argument 2 of `max`, found str",
+        ]
+    );
+
+    // in an Env, calls are checked against its native functions and those
+    // its programs defined, but not against the builtins it lacks
+    let env = EnvBuilder::new().capabilities(Capabilities::none()).build();
+    env.borrow_mut()
+        .register_fn("pad", |s: String, n: i64| format!("{:1$}", s, n as usize));
+    Env::eval(
+        env.clone(),
+        &Parser::new("fn half(n: num) -> num: n / 2;").parse(),
+    )
+    .unwrap();
+    let errors_in = |source: &str| {
+        checker::check_in(&Parser::new(source).parse(), &env.borrow())
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        errors_in("pad(1, \"a\") + 1, half(\"a\"), upper(1), print(1) + 1"),
+        [
+            "type error at 1:1: `+` can't be applied to str and num",
+            "type error at 1:5: expected str for argument 1 of `pad`, found num",
+            "type error at 1:8: expected num for argument 2 of `pad`, found str",
+            "type error at 1:23: expected num for parameter `n` of `half`, found str",
+            "type error at 1:35: expected str for argument 1 of `upper`, found num",
+        ]
+    );
+    assert_eq!(
+        errors_in("fn half(s: str): s; half(1)"),
+        ["type error at 1:26: expected str for parameter `s` of `half`, found num"]
+    );
+
+    let syntax_error = |source: &str| Parser::new(source).try_parse().unwrap_err().to_string();
+    assert_eq!(
+        syntax_error("fn f(a: int): a;"),
+        "syntax error at 1:9: expected a type, found `int`"
+    );
+    assert_eq!(
+        syntax_error("x: num"),
+        "syntax error at 1:7: unexpected end of input"
+    );
+
+    let script = scratch_dir("type_annotations").join("mismatch.kotoba");
+    std::fs::write(&script, "println(\"before\"),\nx: str = 1 + 2").unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_file_eval"))
+        .arg(&script)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "type error at 2:10: expected str for `x`, found num\n"
+    );
+}
+
+#[test]
+fn conversions() {
+    eval_eq("str(1.5)", string("1.5"));
+    eval_eq("str(\"a\")", string("a"));
+    eval_eq("str([1, \"a\"])", string("[1, \"a\"]"));
+    eval_eq("str(nil)", string("nil"));
+    eval_eq("num(\" 2.5 \")", Type::Number(2.5));
+    eval_eq("num(\"1e3\")", Type::Number(1000.0));
+    eval_eq("num(\"abc\")", Type::Nil);
+    eval_eq("num(\"inf\")", Type::Nil);
+    eval_eq("num(\"\")", Type::Nil);
+    eval_eq("num(true)", Type::Number(1.0));
+    eval_eq("num([1])", Type::Nil);
+    eval_eq("int(\"-3.7\")", Type::Number(-3.0));
+    eval_eq("int(\"x\")", Type::Nil);
+    eval_eq("is_nil(num(\"x\"))", Type::Boolean(true));
+    eval_eq("is_nil(0)", Type::Boolean(false));
+}
+
+#[test]
+fn truthiness() {
+    eval_eq(
+        "[bool(0), bool(\"\"), bool([]), bool(nil), bool(false)]",
+        Type::List(vec![Type::Boolean(false); 5]),
+    );
+    eval_eq(
+        "[bool(-1), bool(\"0\"), bool([nil]), bool(true)]",
+        Type::List(vec![Type::Boolean(true); 4]),
+    );
+}
+
+#[test]
+fn type_introspection() {
+    eval_eq(
+        "[type_of(1), type_of(true), type_of(\"\"), type_of([]), type_of(nil)]",
+        strings(&["number", "boolean", "string", "list", "nil"]),
+    );
+}
+
+/// Returns a fresh, empty directory for a test to write files into.
+fn scratch_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("kotoba-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn file_io() {
+    let dir = scratch_dir("file_io");
+    let file = dir.join("out.txt");
+    let source = format!(
+        "p = \"{file}\", \
+         [write_file(p, \"a\"), append_file(p, 1), read_file(p), exists(p), list_dir(\"{dir}\")]",
+        file = file.display(),
+        dir = dir.display(),
+    );
+
+    eval_eq(
+        &source,
+        Type::List(vec![
+            Type::Boolean(true),
+            Type::Boolean(true),
+            string("a1"),
+            Type::Boolean(true),
+            strings(&["out.txt"]),
+        ]),
+    );
+
+    let missing = dir.join("missing");
+    let source = format!(
+        "p = \"{}\", [read_file(p), exists(p), list_dir(p), write_file(p + \"/x\", \"\")]",
+        missing.display()
+    );
+    eval_eq(
+        &source,
+        Type::List(vec![
+            Type::Nil,
+            Type::Boolean(false),
+            Type::Nil,
+            Type::Boolean(false),
+        ]),
+    );
+}
+
+/// A `Write` sink that can still be read after being handed to an `Env`.
+#[derive(Clone, Default)]
+struct SharedBuffer(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+#[test]
+fn capabilities() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let withheld = |env: &Rc<RefCell<Env>>, source: &str| {
+        matches!(
+            Env::eval(env.clone(), &Parser::new(source).parse()),
+            Err(RuntimeError::UndefinedFunction { .. })
+        )
+    };
+
+    let env = EnvBuilder::new().capabilities(Capabilities::none()).build();
+    assert!(withheld(&env, "exists(\".\")"));
+    assert!(withheld(&env, "time()"));
+    assert!(withheld(&env, "args()"));
+    assert_eq!(
+        Env::eval(env, &Parser::new("sqrt(4)").parse()),
+        Ok(Type::Number(2.0))
+    );
+
+    let env = EnvBuilder::new()
+        .capabilities(Capabilities {
+            fs: false,
+            ..Capabilities::default()
+        })
+        .args(vec!["a".to_string()])
+        .build();
+    assert!(withheld(&env, "exists(\".\")"));
+    let ast = Parser::new("[type_of(time()), args(), sqrt(4)]").parse();
+    assert_eq!(
+        Env::eval(env, &ast).unwrap(),
+        Type::List(vec![string("number"), strings(&["a"]), Type::Number(2.0)])
+    );
+
+    // scripts may print without reading the standard input
+    let out = SharedBuffer::default();
+    let env = EnvBuilder::new()
+        .capabilities(Capabilities {
+            stdin: false,
+            ..Capabilities::default()
+        })
+        .output(out.clone())
+        .build();
+    assert!(withheld(&env, "input(\"name? \")"));
+    assert!(withheld(&env, "read_line()"));
+    Env::eval(env, &Parser::new("println(\"hi\")").parse()).unwrap();
+    assert_eq!(out.contents(), "hi\n");
+}
+
+#[test]
+fn undefined_names() {
+    let error = |source: &str| {
+        Env::eval(Env::new(), &Parser::new(source).parse())
+            .unwrap_err()
+            .to_string()
+    };
+
+    assert_eq!(
+        error("pritnln(1)"),
+        "undefined function `pritnln`; did you mean `println`?"
+    );
+    assert_eq!(
+        error("fn area(w, h): ret w * h;\nwidth = 2, aera(widht, 3)"),
+        "undefined variable `widht`; did you mean `width`?"
+    );
+    assert_eq!(
+        error("fn area(w, h): ret w * h;\nwidth = 2, aera(width, 3)"),
+        "undefined function `aera`; did you mean `area`?"
+    );
+    assert_eq!(
+        error("fn f(count): ret coutn;\nf(1)"),
+        "undefined variable `coutn`; did you mean `count`?"
+    );
+    assert_eq!(
+        error("ab = 1, ac = 2, print(ad)"),
+        "undefined variable `ad`; did you mean `ab` or `ac`?"
+    );
+    assert_eq!(error("xyzzy"), "undefined variable `xyzzy`");
+    assert_eq!(error("y = 1, x"), "undefined variable `x`");
+    assert_eq!(
+        Env::eval(Env::new(), &Parser::new("nope()").parse()),
+        Err(RuntimeError::UndefinedFunction {
+            name: "nope".to_owned(),
+            suggestions: vec![],
+        })
+    );
+}
+
+#[test]
+fn output_sink() {
+    let out = SharedBuffer::default();
+    let env = EnvBuilder::new().output(out.clone()).build();
+    let ast = Parser::new("print(1, \"a\"), println([\"b\"]), println()").parse();
+    Env::eval(env, &ast).unwrap();
+
+    assert_eq!(out.contents(), "1a[\"b\"]\n\n");
+}
+
+fn eval_limited(source: &str, limits: Limits) -> Result<Type, RuntimeError> {
+    let env = EnvBuilder::new().limits(limits).build();
+    Env::eval(env, &Parser::new(source).parse())
+}
+
+#[test]
+fn execution_limits() {
+    let steps = Limits {
+        max_steps: Some(1000),
+        ..Limits::default()
+    };
+    assert_eq!(
+        eval_limited("while true: nil;", steps),
+        Err(RuntimeError::LimitExceeded(Limit::Steps(1000)))
+    );
+    assert!(eval_limited("i = 0, while i < 10: nonlocal i = i + 1;, i", steps).is_ok());
+
+    let depth = Limits {
+        max_call_depth: Some(20),
+        ..Limits::default()
+    };
+    assert_eq!(
+        eval_limited("fn f(n): ret f(n + 1); f(0)", depth),
+        Err(RuntimeError::LimitExceeded(Limit::CallDepth(20)))
+    );
+    assert_eq!(
+        eval_limited("fn f(n): if n > 0: ret f(n - 1); ret n; f(10)", depth),
+        Ok(Type::Number(0.0))
+    );
+    assert_eq!(
+        eval_limited("fn f(n): ret f(n + 1); f(0)", Limits::default()),
+        Err(RuntimeError::LimitExceeded(Limit::CallDepth(10_000)))
+    );
+    let unbounded = Limits {
+        max_call_depth: None,
+        ..Limits::default()
+    };
+    assert_eq!(
+        eval_limited(
+            "fn sum(n): if n == 0: ret 0; ret n + sum(n - 1); sum(20000)",
+            unbounded
+        ),
+        Ok(Type::Number(200_010_000.0))
+    );
+
+    let size = Limits {
+        max_value_size: Some(1000),
+        ..Limits::default()
+    };
+    assert_eq!(
+        eval_limited("repeat(\"a\", 2000)", size),
+        Err(RuntimeError::LimitExceeded(Limit::ValueSize(1000)))
+    );
+    assert_eq!(
+        eval_limited("s = \"a\", while true: nonlocal s = s + s;", size),
+        Err(RuntimeError::LimitExceeded(Limit::ValueSize(1000)))
+    );
+    assert!(eval_limited("repeat(\"a\", 100)", size).is_ok());
+
+    // checked before the strings get built
+    for source in [
+        "n = 100000000000, repeat(\"ab\", n)",
+        "join([\"a\", \"b\", \"c\"], repeat(\"-\", 900))",
+        "replace(repeat(\"a\", 900), \"a\", \"bb\")",
+    ] {
+        assert_eq!(
+            eval_limited(source, size),
+            Err(RuntimeError::LimitExceeded(Limit::ValueSize(1000)))
+        );
+    }
+    assert_eq!(
+        eval_limited("repeat(\"ab\", 10 ** 19)", Limits::default())
+            .unwrap_err()
+            .to_string(),
+        "repeat: the result would be too long"
+    );
+}
+
+#[test]
+fn cancellation() {
+    let env = Env::new();
+    let handle = env.borrow().cancel_handle();
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        handle.cancel();
+    });
+
+    let result = Env::eval(env.clone(), &Parser::new("while true: nil;").parse());
+    canceller.join().unwrap();
+    assert_eq!(result, Err(RuntimeError::Cancelled));
+
+    // the handle is re-armed for the next evaluation
+    assert_eq!(
+        Env::eval(env.clone(), &Parser::new("1 + 1").parse()),
+        Ok(Type::Number(2.0))
+    );
+
+    let handle = env.borrow().cancel_handle();
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        handle.cancel();
+    });
+    let started = std::time::Instant::now();
+    let result = Env::eval(env, &Parser::new("sleep(60)").parse());
+    canceller.join().unwrap();
+    assert_eq!(result, Err(RuntimeError::Cancelled));
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+}
+
+#[test]
+fn native_functions() {
+    use std::{cell::RefCell, rc::Rc};
+
+    let log = Rc::new(RefCell::new(vec![]));
+    let env = Env::new();
+    {
+        let mut env = env.borrow_mut();
+        env.register_fn("hypot", |a: f64, b: f64| a.hypot(b));
+        env.register_fn("shout", |s: String, times: i64| {
+            if times < 0 {
+                Err(format!("negative count {}", times))
+            } else {
+                Ok(s.to_uppercase().repeat(times as usize))
+            }
+        });
+        env.register_fn("sum", |xs: Vec<f64>| xs.iter().sum::<f64>());
+        env.register_fn("or_zero", |n: Option<f64>| n.unwrap_or(0.0));
+        env.register_fn("answer", || 42.0);
+        let log = log.clone();
+        env.register_fn("log", move |v: Type| log.borrow_mut().push(v));
+    }
+
+    let eval = |source: &str| Env::eval(env.clone(), &Parser::new(source).parse());
+    assert_eq!(eval("hypot(3, 4)"), Ok(Type::Number(5.0)));
+    assert_eq!(eval("shout(\"ab\", 2)"), Ok(string("ABAB")));
+    assert_eq!(eval("sum([1, 2, 3])"), Ok(Type::Number(6.0)));
+    assert_eq!(eval("or_zero(nil) + or_zero(2)"), Ok(Type::Number(2.0)));
+    assert_eq!(eval("answer()"), Ok(Type::Number(42.0)));
+    assert_eq!(eval("log(1), log(\"a\")"), Ok(Type::Nil));
+    assert_eq!(*log.borrow(), vec![Type::Number(1.0), string("a")]);
+
+    let err = eval("hypot(1)").unwrap_err();
+    assert_eq!(
+        err,
+        RuntimeError::Arity {
+            function: "hypot".to_owned(),
+            expected: Arity::exactly(2),
+            found: 1,
+        }
+    );
+    assert_eq!(err.to_string(), "`hypot` takes 2 arguments but 1 was given");
+
+    let err = eval("shout(\"a\", 1.5)").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "argument 2 of `shout` must be an integer, found number"
+    );
+    let err = eval("sum([1, \"a\"])").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "argument 1 of `sum` must be a list, each item being a number, found list"
+    );
+    let err = eval("shout(\"a\", -1)").unwrap_err();
+    assert_eq!(
+        err,
+        RuntimeError::Native {
+            function: "shout".to_owned(),
+            message: "negative count -1".to_owned(),
+        }
+    );
+}
+
+#[test]
+fn host_objects() {
+    use std::{cell::Cell, rc::Rc};
+
+    struct Account {
+        owner: String,
+        balance: Cell<f64>,
+    }
+
+    let accounts = Class::new("Account")
+        .method("deposit", |a: Rc<Account>, amount: f64| {
+            a.balance.set(a.balance.get() + amount)
+        })
+        .method("balance", |a: Rc<Account>| a.balance.get())
+        .display(|a: &Account| format!("Account({})", a.owner))
+        .equality(|a: &Account, b: &Account| a.owner == b.owner)
+        .build();
+    let counters = Class::new("Counter").build();
+
+    let shared = Rc::new(Account {
+        owner: "host".to_owned(),
+        balance: Cell::new(0.0),
+    });
+
+    let env = Env::new();
+    {
+        let mut env = env.borrow_mut();
+        let handle = accounts.clone();
+        env.register_fn("open_account", move |owner: String| {
+            handle.wrap(Account {
+                owner,
+                balance: Cell::new(0.0),
+            })
+        });
+        let shared = shared.clone();
+        env.register_fn("shared_account", move || accounts.wrap_rc(shared.clone()));
+        env.register_fn("new_counter", move || counters.wrap(()));
+        env.register_fn("owner_of", |a: Rc<Account>| a.owner.clone());
+    }
+
+    let eval = |source: &str| Env::eval(env.clone(), &Parser::new(source).parse());
+    assert_eq!(
+        eval("a = open_account(\"ann\"), a.deposit(5), a.deposit(2.5), a.balance()"),
+        Ok(Type::Number(7.5))
+    );
+    assert_eq!(
+        eval("open_account(\"bob\").balance()"),
+        Ok(Type::Number(0.0))
+    );
+    assert_eq!(
+        eval("\"{open_account(\"bob\")} {[new_counter()]}\""),
+        Ok(string("Account(bob) [<Counter>]"))
+    );
+    assert_eq!(
+        eval("type_of(open_account(\"bob\"))"),
+        Ok(string("Account"))
+    );
+    assert_eq!(eval("owner_of(open_account(\"bob\"))"), Ok(string("bob")));
+
+    assert_eq!(
+        eval("open_account(\"ann\") == open_account(\"ann\")"),
+        Ok(Type::Boolean(true))
+    );
+    assert_eq!(
+        eval("open_account(\"ann\") == open_account(\"bob\")"),
+        Ok(Type::Boolean(false))
+    );
+    assert_eq!(
+        eval("c = new_counter(), [c == c, c == new_counter(), c == 1]"),
+        Ok(Type::List(vec![
+            Type::Boolean(true),
+            Type::Boolean(false),
+            Type::Boolean(false)
+        ]))
+    );
+
+    eval("shared_account().deposit(10), shared_account().deposit(1)").unwrap();
+    assert_eq!(shared.balance.get(), 11.0);
+
+    let error = |source: &str| eval(source).unwrap_err().to_string();
+    assert_eq!(
+        error("open_account(\"a\").withdraw(1)"),
+        "Account has no method `withdraw`"
+    );
+    assert_eq!(error("\"a\".len()"), "string has no method `len`");
+    assert_eq!(
+        error("open_account(\"a\").deposit()"),
+        "`Account.deposit` takes 1 argument but 0 were given"
+    );
+    assert_eq!(
+        error("open_account(\"a\").deposit(\"x\")"),
+        "argument 1 of `Account.deposit` must be a number, found string"
+    );
+    assert_eq!(
+        error("owner_of(new_counter())"),
+        "argument 1 of `owner_of` must be an object of type Account, found Counter"
+    );
+}
+
+#[test]
+fn calling_from_rust() {
+    let env = Env::new();
+    let script = Parser::new(
+        "greeting = \"hello\", \
+         handled = 0, \
+         fn handle(request): nonlocal handled = handled + 1, ret \"{greeting}, {request}\";",
+    )
+    .parse();
+    Env::eval(env.clone(), &script).unwrap();
+
+    let handle = |request: &str| Env::call(env.clone(), "handle", vec![string(request)]);
+    assert_eq!(handle("ann"), Ok(string("hello, ann")));
+    assert_eq!(handle("bob"), Ok(string("hello, bob")));
+    assert_eq!(env.borrow().get("handled"), Some(Type::Number(2.0)));
+
+    env.borrow_mut().set("greeting", "bye");
+    assert_eq!(handle("ann"), Ok(string("bye, ann")));
+    assert_eq!(env.borrow().get("request"), None);
+    assert_eq!(
+        env.borrow().get("PI"),
+        Some(Type::Number(std::f64::consts::PI))
+    );
+
+    assert_eq!(
+        Env::call(env.clone(), "upper", vec![string("a")]),
+        Ok(string("A"))
+    );
+    assert_eq!(
+        Env::call(env.clone(), "handle", vec![])
+            .unwrap_err()
+            .to_string(),
+        "`handle` takes 1 argument but 0 were given"
+    );
+    let err = Env::call(env, "missing", vec![]).unwrap_err();
+    assert_eq!(err, RuntimeError::NoSuchFunction("missing".to_owned()));
+    assert_eq!(err.to_string(), "no function named `missing`");
+
+    let limited = EnvBuilder::new()
+        .limits(Limits {
+            max_steps: Some(100),
+            ..Limits::default()
+        })
+        .build();
+    let script = Parser::new("fn spin(n): i = 0, while i < n: nonlocal i = i + 1;;").parse();
+    Env::eval(limited.clone(), &script).unwrap();
+    for _ in 0..3 {
+        assert!(Env::call(limited.clone(), "spin", vec![Type::Number(5.0)]).is_ok());
+    }
+    assert_eq!(
+        Env::call(limited, "spin", vec![Type::Number(100.0)]),
+        Err(RuntimeError::LimitExceeded(Limit::Steps(100)))
+    );
+
+    // native functions may call back into the program that called them
+    let env = EnvBuilder::new()
+        .limits(Limits {
+            max_steps: Some(1000),
+            ..Limits::default()
+        })
+        .build();
+    let weak = std::rc::Rc::downgrade(&env);
+    env.borrow_mut().register_fn("callback", move |n: f64| {
+        Env::call(weak.upgrade().unwrap(), "inner", vec![Type::Number(n)])
+    });
+    let script =
+        Parser::new("fn inner(n): ret n + 1; n = 0, while n < 5: nonlocal n = callback(n);")
+            .parse();
+    Env::eval(env.clone(), &script).unwrap();
+    assert_eq!(env.borrow().get("n"), Some(Type::Number(5.0)));
+    let spin = Parser::new("while true: callback(1);").parse();
+    assert!(Env::eval(env.clone(), &spin)
+        .unwrap_err()
+        .to_string()
+        .ends_with("exceeded the limit of 1000 evaluation steps"));
+    assert_eq!(
+        Env::call(env, "callback", vec![Type::Number(1.0)]),
+        Ok(Type::Number(2.0))
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_conversions() {
+    let value = to_value(&(1.5, vec![Some("a"), None], true, ())).unwrap();
+    assert_eq!(
+        value,
+        Type::List(vec![
+            Type::Number(1.5),
+            Type::List(vec![string("a"), Type::Nil]),
+            Type::Boolean(true),
+            Type::Nil
+        ])
+    );
+
+    let back: (f64, Vec<Option<String>>, bool, ()) = from_value(&value).unwrap();
+    assert_eq!(back, (1.5, vec![Some("a".to_owned()), None], true, ()));
+    assert_eq!(from_value::<u32>(&Type::Number(3.0)).unwrap(), 3);
+    assert!(from_value::<u32>(&Type::Number(3.5)).is_err());
+    assert!(from_value::<String>(&Type::Number(3.0)).is_err());
+
+    let mut map = std::collections::HashMap::new();
+    map.insert("key", 1);
+    assert!(to_value(&map).is_err());
+
+    let env = Env::new();
+    env.borrow_mut()
+        .set("doc", "[1, 2.5, \"a\", true, null, [\"\\u00e9\"]]");
+    let ast = Parser::new("json_parse(doc)").parse();
+    assert_eq!(
+        Env::eval(env, &ast),
+        Ok(Type::List(vec![
+            Type::Number(1.0),
+            Type::Number(2.5),
+            string("a"),
+            Type::Boolean(true),
+            Type::Nil,
+            Type::List(vec![string("\u{e9}")]),
+        ]))
+    );
+    eval_eq("json_parse(\"[1,\")", Type::Nil);
+    eval_eq("json_parse(\"{{}}\")", Type::Nil);
+    eval_eq(
+        "json_stringify([1, 2.5, \"a\\nb\", nil, false])",
+        string("[1,2.5,\"a\\\\nb\",null,false]"),
+    );
+    eval_eq("json_stringify([1], true)", string("[\n  1\n]"));
+    eval_eq(
+        "json_parse(json_stringify([[\"x\"], -3]))",
+        Type::List(vec![Type::List(vec![string("x")]), Type::Number(-3.0)]),
+    );
+}
+
+#[test]
+fn standard_input() {
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    let script = scratch_dir("standard_input").join("echo.kotoba");
+    std::fs::write(
+        &script,
+        "name = input(\"name? \"), line = read_line(), print(name, \"|\", line, \"|\", read_line())",
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_file_eval"))
+        .arg(&script)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"kotoba\r\nsecond\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "name? kotoba|second|nil"
+    );
+}
+
+#[test]
+fn ast_dumps() {
+    let ast = Parser::new("fn f(a): ret -a ** 2; x = [f(1), \"s\"].len()").parse();
+    assert_eq!(
+        ast.to_sexpr(),
+        concat!(
+            "(program\n",
+            "  (fn f (a) (block (ret (- (** a 2)))))\n",
+            "  (= x (method len (list (call f 1) \"s\"))))"
+        )
+    );
+
+    let ast = Parser::new("if true:\n  x = 1;").parse();
+    assert_eq!(ast.to_sexpr(), "(program (if true (block (= x 1))))");
+    assert_eq!(
+        ast.to_json(),
+        concat!(
+            r#"{"kind":"ProgramRoot","stmts":[{"kind":"IfStmt","#,
+            r#""condition":{"kind":"Boolean","value":true,"#,
+            r#""span":{"start":{"line":1,"character":4},"end":{"line":1,"character":8}}},"#,
+            r#""then_body":{"kind":"Program","stmts":[{"kind":"Assignment","identifier":"x","#,
+            r#""operand":{"kind":"Number","value":1,"#,
+            r#""span":{"start":{"line":2,"character":7},"end":{"line":2,"character":8}}},"#,
+            r#""nonlocal":false,"annotation":null,"#,
+            r#""span":{"start":{"line":2,"character":3},"end":{"line":2,"character":8}}}],"#,
+            r#""span":{"start":{"line":2,"character":3},"end":{"line":2,"character":8}}},"#,
+            r#""else_body":null,"#,
+            r#""span":{"start":{"line":1,"character":1},"end":{"line":2,"character":9}}}],"#,
+            r#""span":{"start":{"line":1,"character":1},"end":{"line":2,"character":9}}}"#
+        )
+    );
+
+    let long = Parser::new("while i < 10: println(\"a long line of output\"), nonlocal i = i + 1;")
+        .parse();
+    assert_eq!(
+        long.to_sexpr(),
+        concat!(
+            "(program\n",
+            "  (while\n",
+            "    (< i 10)\n",
+            "    (block (call println \"a long line of output\") (nonlocal= i (+ i 1)))))"
+        )
+    );
+}
+
+#[test]
+fn file_eval_dumps() {
+    use std::process::Command;
+
+    let script = scratch_dir("file_eval_dumps").join("dump.kotoba");
+    std::fs::write(&script, "x = 1,\nprint(x)").unwrap();
+
+    let run = |flag: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_file_eval"))
+            .arg(flag)
+            .arg(&script)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(run("--dump-ast"), "(program (= x 1) (call print x))\n");
+    assert!(run("--dump-ast=json").starts_with("{\"kind\":\"ProgramRoot\",\"stmts\":["));
+    assert!(run("--dump-ast=debug").starts_with("ProgramRoot(\n"));
+    assert_eq!(
+        run("--dump-tokens"),
+        "1:1-1:2 Identifier(\"x\")\n\
+         1:3-1:4 Equal\n\
+         1:5-1:6 Number(1.0)\n\
+         1:6-1:7 Comma\n\
+         2:1-2:6 Identifier(\"print\")\n\
+         2:6-2:7 OpenParen\n\
+         2:7-2:8 Identifier(\"x\")\n\
+         2:8-2:9 CloseParen\n"
+    );
+
+    // ast.txt is the dump of the fizzbuzz sample
+    let output = Command::new(env!("CARGO_BIN_EXE_file_eval"))
+        .args(["--dump-ast=debug", "sample_code/fizzbuzz.kotoba"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        std::fs::read_to_string("ast.txt").unwrap()
+    );
+
+    // a syntax error dumps no tree, and runs nothing
+    let broken = script.with_file_name("broken.kotoba");
+    std::fs::write(&broken, "x = (1").unwrap();
+    for flags in [&["--dump-ast=json"][..], &["--dump-ast"], &[]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_file_eval"))
+            .args(flags)
+            .arg(&broken)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert!(output.stdout.is_empty());
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "syntax error at 1:5: unclosed `(`\n"
+        );
+    }
+    let output = Command::new(env!("CARGO_BIN_EXE_file_eval"))
+        .args(["--dump-ast=yaml", "missing.kotoba"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("unknown AST format yaml\n"));
+}
+
+#[test]
+fn comments() {
+    eval_eq(
+        "// a comment\nx = 1, // another\n// x = 2,\nx",
+        Type::Number(1.0),
+    );
+    eval_eq("\"// not a comment\"", string("// not a comment"));
+
+    let mut parser = Parser::new("x = 1 // one\n// two");
+    parser.parse();
+    let comments = parser
+        .comments()
+        .iter()
+        .map(|c| format!("{} {}", c.position, c.text))
+        .collect::<Vec<_>>();
+    assert_eq!(comments, ["1:7 // one", "2:1 // two"]);
+}
+
+#[test]
+fn syntax_errors() {
+    let error = |source: &str| Parser::new(source).try_parse().unwrap_err().to_string();
+
+    assert_eq!(error("x = (1"), "syntax error at 1:5: unclosed `(`");
+    assert_eq!(
+        error("if x print(x);"),
+        "syntax error at 1:1: missing `:` after `if`"
+    );
+    assert_eq!(error("x = 1;"), "syntax error at 1:6: unexpected `;`");
+    assert_eq!(error("x ="), "syntax error at 1:4: unexpected end of input");
+    assert_eq!(
+        error("x = 1, nonlocal x"),
+        "syntax error at 1:8: expected an assignment after `nonlocal`"
+    );
+    // the string closes the code of an interpolation
+    assert_eq!(
+        error("\"{}\""),
+        "syntax error at 1:3: expected an expression before `}`"
+    );
+    assert_eq!(
+        error("\"a {1 +}\" + 1"),
+        "syntax error at 1:8: expected an expression before `}`"
+    );
+    assert_eq!(error("\"{(1}\""), "syntax error at 1:3: unclosed `(`");
+    assert!(Parser::new("x = 1, y = 2").try_parse().is_ok());
+}
+
+#[test]
+fn lexical_errors() {
+    let error = |source: &str| Parser::new(source).try_parse().unwrap_err();
+
+    let unclosed = error("x = \"abc");
+    assert_eq!(
+        unclosed.to_string(),
+        "syntax error at 1:5: unclosed string literal"
+    );
+    assert!(unclosed.incomplete);
+    assert_eq!(
+        error("x = \"{\"").to_string(),
+        "syntax error at 1:5: unclosed `{` in string literal; write `{{` for a literal `{`"
+    );
+    assert_eq!(
+        error("if x $ 1: 2;").to_string(),
+        "syntax error at 1:6: unrecognized character `$`"
+    );
+    assert_eq!(
+        error("x = 1 + é").to_string(),
+        "syntax error at 1:9: unrecognized character `é`"
+    );
+    assert!(!error("x = 1 + é").incomplete);
+    assert_eq!(
+        error("\"{1 + $}\"").to_string(),
+        "syntax error at 1:7: unrecognized character `$`"
+    );
+}
+
+#[test]
+fn formatting() {
+    use kotoba::formatter::format;
+
+    let source = "// header\nx=1,   // one\n\n\n\
+                  s=\"a{x+1}{{\" ,fn f(a,b) :\n  // inside\n  ret a+b ; // after\n\
+                  z = match x : 1 => \"one\" , _ if x>2 => \"big\", [a, \"{{\"] => a ;\n\
+                  if x: y = 2\nelse\n   y = 3 ; w = if x: 1 else 2;\n\
+                  while x < 10: println(\"a line long enough to need breaking\"), \
+                  nonlocal x = x + 1;\n\
+                  n = -(1) ** 2 , m = !true,\n\n// the end";
+    let formatted = format(source).unwrap();
+    assert_eq!(
+        formatted,
+        "// header
+x = 1, // one
+
+s = \"a{x + 1}{{\",
+fn f(a, b):
+    // inside
+    ret a + b; // after
+z = match x: 1 => \"one\", _ if x > 2 => \"big\", [a, \"{{\"] => a;
+if x:
+    y = 2
+else
+    y = 3;
+w = if x: 1 else 2;
+while x < 10:
+    println(\"a line long enough to need breaking\"),
+    nonlocal x = x + 1;
+n = -(1) ** 2,
+m = !true
+
+// the end
+"
+    );
+    assert_eq!(format(&formatted).unwrap(), formatted);
+
+    let ast = |source: &str| format!("{:?}", Parser::new(source).parse());
+    assert_eq!(ast(&formatted), ast(source));
+
+    for entry in std::fs::read_dir("sample_code").unwrap() {
+        let source = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        assert_eq!(format(&source).unwrap(), source);
+    }
+
+    assert_eq!(
+        format("x = 1;").unwrap_err().to_string(),
+        "syntax error at 1:6: unexpected `;`"
+    );
+}
+
+#[test]
+fn kotobafmt() {
+    use std::process::Command;
+
+    let dir = scratch_dir("kotobafmt");
+    let script = dir.join("messy.kotoba");
+    std::fs::write(&script, "x=1,print( x )").unwrap();
+
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_kotobafmt"))
+            .args(args)
+            .arg(&script)
+            .status()
+            .unwrap()
+            .code()
+    };
+
+    assert_eq!(run(&["--check"]), Some(1));
+    assert_eq!(std::fs::read_to_string(&script).unwrap(), "x=1,print( x )");
+    assert_eq!(run(&[]), Some(0));
+    assert_eq!(
+        std::fs::read_to_string(&script).unwrap(),
+        "x = 1,\nprint(x)\n"
+    );
+    assert_eq!(run(&["--check"]), Some(0));
+
+    let samples = Command::new(env!("CARGO_BIN_EXE_kotobafmt"))
+        .arg("--check")
+        .arg("sample_code/even_odd.kotoba")
+        .arg("sample_code/fizzbuzz.kotoba")
+        .status()
+        .unwrap();
+    assert!(samples.success());
+
+    std::fs::write(&script, "x = (1").unwrap();
+    assert_eq!(run(&[]), Some(2));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn language_server() {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        process::{Command, Stdio},
+    };
+
+    let mut server = Command::new(env!("CARGO_BIN_EXE_lsp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut input = server.stdin.take().unwrap();
+    let mut output = BufReader::new(server.stdout.take().unwrap());
+
+    let mut send = |message: String| {
+        write!(
+            input,
+            "Content-Length: {}\r\n\r\n{}",
+            message.len(),
+            message
+        )
+        .unwrap();
+        input.flush().unwrap();
+    };
+    let mut receive = || {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            output.read_line(&mut line).unwrap();
+            match line.trim_end().strip_prefix("Content-Length: ") {
+                Some(value) => length = value.parse().unwrap(),
+                None if line.trim_end().is_empty() => break,
+                None => {}
+            }
+        }
+        let mut body = vec![0; length];
+        output.read_exact(&mut body).unwrap();
+        String::from_utf8(body).unwrap()
+    };
+    let text = |source: &str| source.replace('"', "\\\"").replace('\n', "\\n");
+    let request = |id: usize, method: &str, params: &str| {
+        format!(
+            r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{}}}"#,
+            id, method, params
+        )
+    };
+    let at = |line: usize, character: usize| {
+        format!(
+            concat!(
+                r#"{{"textDocument":{{"uri":"file:///a.kotoba"}},"#,
+                r#""position":{{"line":{},"character":{}}}}}"#
+            ),
+            line, character
+        )
+    };
+
+    send(request(1, "initialize", r#"{"capabilities":{}}"#));
+    let response = receive();
+    assert!(
+        response.contains(r#""definitionProvider":true"#),
+        "{}",
+        response
+    );
+
+    let source = "fn area(w, h): ret w * h;\n\
+                  size = area(2, 3),\n\
+                  match size > 5: true => print(size);";
+    send(format!(
+        concat!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"#,
+            r#""uri":"file:///a.kotoba","languageId":"kotoba","version":1,"text":"{}"}}}}}}"#
+        ),
+        text(source)
+    ));
+    assert_eq!(
+        receive(),
+        concat!(
+            r#"{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"#,
+            r#""uri":"file:///a.kotoba","diagnostics":[{"range":{"#,
+            r#""start":{"line":2,"character":0},"end":{"line":2,"character":5}},"#,
+            r#""severity":2,"source":"kotoba","#,
+            r#""message":"non-exhaustive match: `false` not covered"}]}}"#
+        )
+    );
+
+    send(request(2, "textDocument/definition", &at(1, 8)));
+    assert_eq!(
+        receive(),
+        concat!(
+            r#"{"jsonrpc":"2.0","id":2,"result":{"uri":"file:///a.kotoba","range":{"#,
+            r#""start":{"line":0,"character":3},"end":{"line":0,"character":7}}}}"#
+        )
+    );
+    send(request(3, "textDocument/definition", &at(0, 19)));
+    assert!(receive().contains(r#""start":{"line":0,"character":8}"#));
+
+    send(request(4, "textDocument/hover", &at(1, 8)));
+    assert!(receive().contains(r#""value":"```kotoba\nfn area(w, h)\n```""#));
+    send(request(5, "textDocument/hover", &at(2, 24)));
+    assert!(receive().contains(r#""value":"`print`: builtin function""#));
+    send(request(6, "textDocument/hover", &at(2, 15)));
+    assert_eq!(receive(), r#"{"jsonrpc":"2.0","id":6,"result":null}"#);
+
+    send(request(7, "textDocument/documentSymbol", &at(0, 0)));
+    let response = receive();
+    assert!(response.contains(r#""name":"area","detail":"fn area(w, h)","kind":12"#));
+
+    send(request(8, "textDocument/completion", &at(0, 0)));
+    let response = receive();
+    for label in &["area", "size", "len", "PI"] {
+        assert!(response.contains(&format!(r#""label":"{}""#, label)));
+    }
+
+    let change = |source: &str| {
+        format!(
+            concat!(
+                r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{"#,
+                r#""textDocument":{{"uri":"file:///a.kotoba","version":2}},"#,
+                r#""contentChanges":[{{"text":"{}"}}]}}}}"#
+            ),
+            text(source)
+        )
+    };
+    // characters count in UTF-16 code units
+    send(change("s = \"é😀\", t = s"));
+    assert!(receive().contains(r#""diagnostics":[]"#));
+    send(request(9, "textDocument/definition", &at(0, 15)));
+    assert!(
+        receive().contains(r#""start":{"line":0,"character":0},"end":{"line":0,"character":1}"#)
+    );
+    send(change("s = \"é😀\", t = s, u = (1"));
+    assert!(receive().contains(r#""start":{"line":0,"character":22}"#));
+
+    send(change("x = (1"));
+    assert!(receive().contains(r#""severity":1,"source":"kotoba","message":"unclosed `(`""#));
+    send(change("x = \"abc"));
+    let diagnostics = receive();
+    assert!(diagnostics.contains(r#""start":{"line":0,"character":4}"#));
+    assert!(diagnostics.contains(r#""message":"unclosed string literal""#));
+
+    send(request(10, "textDocument/formatting", &at(0, 0)));
+    assert!(receive().contains(r#""error":{"code":-32601"#));
+
+    send(request(11, "shutdown", "null"));
+    assert_eq!(receive(), r#"{"jsonrpc":"2.0","id":11,"result":null}"#);
+    send(r#"{"jsonrpc":"2.0","method":"exit"}"#.to_owned());
+    assert_eq!(server.wait().unwrap().code(), Some(0));
+}
+
+#[test]
+fn linting() {
+    use kotoba::lint::{self, Config, Rule};
+
+    let source = "\
+count = 0,
+fn bump(n):
+    count = count + n,
+    ret count,
+    print(\"done\");
+while count: nonlocal count = count - 1;
+if \"yes\": print(undefined_fn(1));
+match 3: x => 1, _ => 2;
+_ignored = 1,
+total = len([1, 2]) + PI";
+    let ast = Parser::new(source).try_parse().unwrap();
+    let report = |config: &Config| {
+        lint::lint(source, &ast, config)
+            .iter()
+            .map(|l| format!("{} {}", l.span.start, l.rule.name()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        report(&Config::default()),
+        [
+            "3:5 shadowing",
+            "5:5 unreachable-code",
+            "7:4 non-boolean-condition",
+            "7:17 unknown-function",
+            "8:10 unused-variable",
+            "10:1 unused-variable",
+        ]
+    );
+
+    let source = "fn f(a): a; f(1, 2), f(1), pow(2), print(1, 2, 3), g(1)";
+    let ast = Parser::new(source).try_parse().unwrap();
+    let lints = lint::lint(source, &ast, &Config::default());
+    let messages = lints.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            "warning at 1:13: `f` takes 1 argument but 2 were given [argument-count]",
+            "warning at 1:28: `pow` takes 2 arguments but 1 was given [argument-count]",
+            "warning at 1:52: call to unknown function `g` [unknown-function]",
+        ]
+    );
+
+    let config = Config::parse("[rules]\nunused-variable = false\nshadowing = false").unwrap();
+    assert_eq!(
+        report(&config),
+        [
+            "5:5 unreachable-code",
+            "7:4 non-boolean-condition",
+            "7:17 unknown-function",
+        ]
+    );
+    assert!(!config.is_enabled(Rule::Shadowing));
+    assert!(Config::parse("[rules]\nshadowing = maybe").is_err());
+
+    // conditions are typed as the checker types them
+    let source = "while len(\"ab\"): nil; if 1 < 2 and !false: nil;";
+    let ast = Parser::new(source).try_parse().unwrap();
+    let lints = lint::lint(source, &ast, &Config::default());
+    let messages = lints.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        ["warning at 1:7: condition is a number, not a boolean [non-boolean-condition]"]
+    );
+}
+
+#[test]
+fn lint_binary() {
+    use std::process::Command;
+
+    let dir = scratch_dir("lint");
+    let script = dir.join("script.kotoba");
+    std::fs::write(&script, "x = 1,\nif 1: print(x);").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lint"))
+        .current_dir(&dir)
+        .arg("script.kotoba")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "script.kotoba:2:4: condition is a number, not a boolean [non-boolean-condition]\n"
+    );
+
+    std::fs::write(
+        dir.join("kotoba-lint.toml"),
+        "non-boolean-condition = false\n",
+    )
+    .unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_lint"))
+        .current_dir(&dir)
+        .arg("script.kotoba")
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[test]
+fn repl() {
+    use kotoba::repl::{is_incomplete, Session};
+    use std::{io::Write, process::Command, process::Stdio};
+
+    assert!(is_incomplete("fn f(a):\n  ret a"));
+    assert!(is_incomplete("x = [1,"));
+    assert!(is_incomplete("\"{x"));
+    assert!(!is_incomplete("fn f(a): ret a;"));
+    assert!(!is_incomplete("x = 1;"));
+    assert!(!is_incomplete("\"{}\""));
+    assert!(!is_incomplete("print(\"{(1 +}\")"));
+
+    let mut session = Session::new();
+    assert_eq!(
+        session.eval("x = 1, if x: 2;"),
+        "runtime error: condition must be a boolean, found number"
+    );
+    assert_eq!(
+        session.eval("s = \"a\", -s"),
+        "runtime error: `-` can't be applied to string"
+    );
+    assert_eq!(
+        session.eval("x and true"),
+        "runtime error: `and` can't be applied to number and boolean"
+    );
+    assert_eq!(
+        session.eval("sqrt(\"4\")"),
+        "type error at 1:6: expected num for argument 1 of `sqrt`, found str"
+    );
+    assert_eq!(
+        session.eval("len(x)"),
+        "runtime error: argument 1 of `len` must be a string or a list, found number"
+    );
+    assert_eq!(session.eval("x"), "=> 1");
+
+    let home = scratch_dir("repl");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_repl"))
+        .env("HOME", &home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"i = 0,\nwhile i < 3:\n  nonlocal i = i + 1;\ni\nx\n(i +\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "=> nil\n=> nil\n=> 3\nruntime error: undefined variable `x`\n\
+         syntax error at 1:5: unexpected end of input\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(home.join(".kotoba_history")).unwrap(),
+        "#V2\ni = 0,\nwhile i < 3:\\n  nonlocal i = i + 1;\ni\nx\n(i +\n"
+    );
+}
+
+#[test]
+fn repl_commands() {
+    use kotoba::repl::Session;
+
+    let script = scratch_dir("repl_commands").join("shapes.kotoba");
+    std::fs::write(&script, "fn area(w: num, h = 2): w * h;\nside = 3").unwrap();
+
+    let mut session = Session::new();
+    assert!(session.eval(":help").contains(":load FILE"));
+    assert_eq!(session.eval("x = [1, 2]"), "=> nil");
+    assert_eq!(
+        session.eval(&format!(":load {}", script.display())),
+        "=> nil"
+    );
+    assert_eq!(
+        session.eval(":env"),
+        "side = 3\nx = [1, 2]\nfn area(w: num, h = 2)"
+    );
+    assert_eq!(session.eval(":ast 1 + x"), "(program (+ 1 x))");
+    assert_eq!(
+        session.eval(":tokens x.len()"),
+        "1:1-1:2 Identifier(\"x\")\n1:2-1:3 Dot\n1:3-1:6 Identifier(\"len\")\n\
+         1:6-1:7 OpenParen\n1:7-1:8 CloseParen"
+    );
+    assert_eq!(session.eval(":type len(x) > 1"), "bool");
+    assert_eq!(session.eval(":type area(side)"), "any");
+    assert_eq!(
+        session.eval(":type area(\"a\")"),
+        "type error at 1:6: expected num for parameter `w` of `area`, found str"
+    );
+    assert_eq!(session.eval("fn label(n: num) -> str: \"#{n}\";"), "=> nil");
+    assert_eq!(session.eval(":type label(side)"), "str");
+    assert_eq!(
+        session.eval(":type side - \"a\""),
+        "type error at 1:1: `-` can't be applied to num and str"
+    );
+    assert!(session.eval(":time area(side)").starts_with("=> 6\ntook "));
+    assert!(session
+        .eval(":load missing.kotoba")
+        .starts_with("error: cannot read missing.kotoba"));
+    assert_eq!(session.eval(":ast"), "usage: :ast CODE");
+    assert_eq!(session.eval(":what"), "unknown command `:what`; see :help");
+
+    assert_eq!(session.eval(":reset"), "");
+    assert_eq!(session.eval(":env"), "");
+    assert_eq!(
+        session.eval("side"),
+        "runtime error: undefined variable `side`"
+    );
+}
+
+#[test]
+fn repl_editing() {
+    use kotoba::repl::{highlight, Session};
+
+    assert_eq!(
+        highlight("fn f(a): ret a * 2; // double"),
+        "\u{1b}[35mfn\u{1b}[0m f(a): \u{1b}[35mret\u{1b}[0m a \u{1b}[36m*\u{1b}[0m \
+         \u{1b}[33m2\u{1b}[0m; \u{1b}[90m// double\u{1b}[0m"
+    );
+    assert_eq!(highlight("print(\"abc"), "print(\u{1b}[32m\"abc\u{1b}[0m");
+    assert_eq!(
+        highlight(":type x > 1"),
+        "\u{1b}[1m:type\u{1b}[0m x \u{1b}[36m>\u{1b}[0m \u{1b}[33m1\u{1b}[0m"
+    );
+    assert_eq!(highlight(":load a-b"), "\u{1b}[1m:load\u{1b}[0m a-b");
+
+    let mut session = Session::new();
+    session.eval("fn fib(n): n; first = 1");
+    assert_eq!(
+        session.complete("fi", 2),
+        (0, vec!["fib".into(), "find".into(), "first".into()])
+    );
+    assert_eq!(session.complete(":type 1 + fi", 12).0, 10);
+    assert_eq!(session.complete(":load fi", 8), (8, vec![]));
+    assert_eq!(session.complete(":re", 3), (1, vec!["reset".into()]));
+    assert_eq!(session.complete("x = ", 4), (4, vec![]));
+}
+
+#[test]
+fn kotoba_cli() {
+    use std::{io::Write, process::Command, process::Stdio};
+
+    let kotoba = |args: &[&str], input: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_kotoba"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        (
+            output.status.code().unwrap(),
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+
+    assert_eq!(kotoba(&["-e", "2 ** 3"], ""), (0, "8\n".into(), "".into()));
+
+    // output cut short by its reader, as with `| head`, isn't an error
+    let mut child = Command::new(env!("CARGO_BIN_EXE_kotoba"))
+        .args(["tokens", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all("x, ".repeat(100_000).as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stderr.is_empty());
+    assert_eq!(
+        kotoba(&["run", "-", "a", "b"], "println(args())"),
+        (0, "[\"a\", \"b\"]\n".into(), "".into())
+    );
+    assert_eq!(
+        kotoba(&["run", "-e", "len(\"ab\") - \"c\""], ""),
+        (
+            1,
+            "".into(),
+            "type error at 1:1: `-` can't be applied to num and str\n".into()
+        )
+    );
+    assert_eq!(
+        kotoba(&["-e", "nope"], "").2,
+        "runtime error: undefined variable `nope`\n"
+    );
+    assert_eq!(
+        kotoba(&["check", "-e", "x = 1", "-"], "(1"),
+        (
+            1,
+            "".into(),
+            "<stdin>: syntax error at 1:1: unclosed `(`\n".into()
+        )
+    );
+    assert_eq!(
+        kotoba(&["ast", "-e", "f(1)"], "").1,
+        "(program (call f 1))\n"
+    );
+    assert_eq!(
+        kotoba(&["tokens", "-"], "x").1,
+        "1:1-1:2 Identifier(\"x\")\n"
+    );
+    assert_eq!(kotoba(&["fmt"], "x=[1,2]").1, "x = [1, 2]\n");
+    assert!(kotoba(&["--help"], "").1.starts_with("usage: kotoba"));
+    let (status, _, error) = kotoba(&["frob"], "");
+    assert_eq!(status, 2);
+    assert!(error.starts_with("unknown command frob\nusage: kotoba"));
+
+    let dir = scratch_dir("kotoba_cli");
+    std::fs::write(dir.join("greet.kotoba"), "println(\"hi\")").unwrap();
+    std::fs::write(dir.join("greet.out"), "hi\n").unwrap();
+    std::fs::write(dir.join("wrong.kotoba"), "print(1)").unwrap();
+    std::fs::write(dir.join("wrong.out"), "2").unwrap();
+    // tested programs can neither stop the runner nor wait for input
+    std::fs::write(dir.join("exit.kotoba"), "exit(0)").unwrap();
+    std::fs::write(dir.join("input.kotoba"), "read_line()").unwrap();
+    let (status, output, _) = kotoba(&["test", dir.to_str().unwrap()], "");
+    assert_eq!(status, 1);
+    assert_eq!(
+        output.replace(dir.to_str().unwrap(), "DIR"),
+        "test DIR/exit.kotoba ... FAILED\nruntime error: undefined function `exit`\n\
+         test DIR/greet.kotoba ... ok\n\
+         test DIR/input.kotoba ... FAILED\n\
+         runtime error: undefined function `read_line`; did you mean `read_file`?\n\
+         test DIR/wrong.kotoba ... FAILED\nexpected output:\n2\nactual output:\n1\n\
+         \n1 passed, 3 failed\n"
+    );
+}